@@ -0,0 +1,32 @@
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use gen_rs::tsp::bench::Fixture;
+
+const INSTANCE: &str = "data/xqf131.tsp";
+const SEED: u64 = 42;
+
+fn fitness(c: &mut Criterion) {
+    let mut fixture = Fixture::load(INSTANCE, 1, SEED);
+    c.bench_function("tsp fitness 131 cities", |b| b.iter(|| fixture.fitness()));
+}
+
+fn selection(c: &mut Criterion) {
+    let mut fixture = Fixture::load(INSTANCE, 500, SEED);
+    c.bench_function("tsp selection 500 genomes", |b| {
+        b.iter(|| fixture.selection(500))
+    });
+}
+
+fn generation(c: &mut Criterion) {
+    let fixture = Fixture::load(INSTANCE, 500, SEED);
+    // every iteration starts from the same population and rng state
+    c.bench_function("tsp generation 500 genomes", |b| {
+        b.iter_batched(
+            || fixture.clone(),
+            |mut fixture| fixture.generation(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, fitness, selection, generation);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use gen_rs::tsp;
+
+// steps the tsp one generation per Enter and plots the best tour, q quits.
+// without a terminal on stdin it runs to the end and plots only the result
+fn main() {
+    let options = tsp::Options {
+        pop_size: 100,
+        generations: 200,
+        ..Default::default()
+    };
+    let mut runner = tsp::Runner::new(1, &options);
+    let interactive = std::io::stdin().is_terminal();
+    let mut lines = std::io::stdin().lock().lines();
+    let mut out = std::io::stdout().lock();
+
+    while runner.step() {
+        if !interactive {
+            continue;
+        }
+        runner.plot(&mut out).expect("failed to plot");
+        write!(
+            out,
+            "generation: {} | best: {:.1} | Enter to step, q to quit ",
+            runner.generation(),
+            runner.best_length()
+        )
+        .expect("failed to write");
+        out.flush().expect("failed to write");
+        match lines.next() {
+            Some(Ok(line)) if line.trim() != "q" => {}
+            _ => return,
+        }
+    }
+
+    runner.plot(&mut out).expect("failed to plot");
+    writeln!(
+        out,
+        "generation: {} | best: {:.1}",
+        runner.generation(),
+        runner.best_length()
+    )
+    .expect("failed to write");
+}
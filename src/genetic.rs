@@ -1,15 +1,1545 @@
-pub trait Selection {
-    fn selection(&self, size: usize) -> Self;
-}
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 pub trait Fitness<T> {
     fn fitness(&self) -> T;
 }
 
 pub trait Mutate {
-    fn mutate(&mut self, n: usize, prob: f64);
+    fn mutate<R: Rng + ?Sized>(&mut self, rng: &mut R, n: usize, prob: f64);
 }
 
 pub trait Crossover {
-    fn crossover(&mut self);
+    fn crossover<R: Rng + ?Sized>(&mut self, rng: &mut R);
+}
+
+pub trait Similarity {
+    fn similarity(&self, other: &Self) -> f64;
+}
+
+// (mu+lambda) lets parents compete with their offspring for a place in the
+// next generation, (mu,lambda) replaces the parents with offspring entirely
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplacementStrategy {
+    Plus,
+    Comma,
+}
+
+impl std::str::FromStr for ReplacementStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "plus" => Ok(ReplacementStrategy::Plus),
+            "comma" => Ok(ReplacementStrategy::Comma),
+            _ => Err(format!("unknown replacement {s}, expected plus or comma")),
+        }
+    }
+}
+
+// how parents are drawn: fitness-weighted sampling, the best of k random
+// genomes, or sampling weighted by position in the sorted population. the
+// last two ignore how close the fitness values are. StochasticUniversal
+// uses the roulette weights but spaces its draws evenly around the wheel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    Roulette,
+    Tournament(usize),
+    Rank,
+    StochasticUniversal,
+    // weights exp(-gap to the best / temperature), see CoolingSchedule
+    Boltzmann,
+    // roulette on fitness rescaled by the spread of the population, see sigma_weight
+    Sigma,
+}
+
+// roulette, tournament:K, rank, sus, boltzmann or sigma
+impl std::str::FromStr for SelectionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.split_once(':') {
+            Some(("tournament", k)) => k
+                .parse()
+                .map(SelectionStrategy::Tournament)
+                .map_err(|_| format!("invalid tournament size {k}")),
+            _ => match s {
+                "roulette" => Ok(SelectionStrategy::Roulette),
+                "rank" => Ok(SelectionStrategy::Rank),
+                "sus" => Ok(SelectionStrategy::StochasticUniversal),
+                "boltzmann" => Ok(SelectionStrategy::Boltzmann),
+                "sigma" => Ok(SelectionStrategy::Sigma),
+                _ => Err(format!(
+                    "unknown selection {s}, expected roulette, tournament:K, rank, sus, boltzmann or sigma"
+                )),
+            },
+        }
+    }
+}
+
+// size parents drawn from genomes, which must be sorted best first. the
+// temperature is only read by Boltzmann
+pub fn select<G: Genome, R: Rng + ?Sized>(
+    genomes: &[G],
+    strategy: SelectionStrategy,
+    temperature: f64,
+    rng: &mut R,
+    size: usize,
+) -> Vec<G> {
+    let sense = G::SENSE;
+    match strategy {
+        SelectionStrategy::Roulette => {
+            let worst = worst(genomes).fitness();
+            genomes
+                .choose_multiple_weighted(rng, size, |genome| {
+                    sense.margin(genome.fitness(), worst) + 1.0
+                })
+                .unwrap()
+                .cloned()
+                .collect()
+        }
+        SelectionStrategy::Tournament(k) => (0..size)
+            .map(|_| tournament(genomes, rng, k).clone())
+            .collect(),
+        SelectionStrategy::StochasticUniversal => {
+            let worst = worst(genomes).fitness();
+            let weights: Vec<f64> = genomes
+                .iter()
+                .map(|genome| sense.margin(genome.fitness(), worst) + 1.0)
+                .collect();
+            stochastic_universal_sampling(rng, &weights, size)
+                .into_iter()
+                .map(|index| genomes[index].clone())
+                .collect()
+        }
+        SelectionStrategy::Boltzmann => {
+            let best = genomes.first().unwrap().fitness();
+            genomes
+                .choose_multiple_weighted(rng, size, |genome| {
+                    boltzmann_weight(sense, genome.fitness(), best, temperature)
+                })
+                .unwrap()
+                .cloned()
+                .collect()
+        }
+        SelectionStrategy::Sigma => {
+            let fitnesses: Vec<f64> = genomes.iter().map(|genome| genome.fitness()).collect();
+            let (mean, std_dev) = mean_std_dev(&fitnesses);
+            genomes
+                .choose_multiple_weighted(rng, size, |genome| {
+                    sigma_weight(sense, genome.fitness(), mean, std_dev)
+                })
+                .unwrap()
+                .cloned()
+                .collect()
+        }
+        SelectionStrategy::Rank => {
+            // the genomes are sorted best first, so the best genome gets
+            // weight n and the worst 1 whatever their fitness values
+            let n = genomes.len();
+            let ranks: Vec<usize> = (0..n).collect();
+            ranks
+                .choose_multiple_weighted(rng, size, |&rank| (n - rank) as f64)
+                .unwrap()
+                .map(|&rank| genomes[rank].clone())
+                .collect()
+        }
+    }
+}
+
+// best of a random sample; Ord puts the better genome first
+pub fn tournament<'a, G: Ord, R: Rng + ?Sized>(
+    genomes: &'a [G],
+    rng: &mut R,
+    size: usize,
+) -> &'a G {
+    genomes.choose_multiple(rng, size).min().unwrap()
+}
+
+// Ord puts the better genome first, so the worst is the maximum
+pub fn worst<G: Ord>(genomes: &[G]) -> &G {
+    genomes.iter().max().unwrap()
+}
+
+// the best n genomes with distinct genes, best first; expects sorted genomes
+pub fn unique_elites<G: Genome>(genomes: &[G], n: usize) -> Vec<G> {
+    let mut elites: Vec<G> = Vec::with_capacity(n);
+    for genome in genomes {
+        if elites.len() == n {
+            break;
+        }
+        if !elites.iter().any(|elite| elite.genes() == genome.genes()) {
+            elites.push(genome.clone());
+        }
+    }
+    elites
+}
+
+// fitness of every genome in genome order, filling the caches on the rayon
+// pool when the parallel feature is on
+#[cfg(feature = "parallel")]
+pub fn evaluate_parallel<G: Genome + Sync>(genomes: &[G]) -> Vec<f64> {
+    genomes.par_iter().map(|genome| genome.fitness()).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn evaluate_parallel<G: Genome + Sync>(genomes: &[G]) -> Vec<f64> {
+    genomes.iter().map(|genome| genome.fitness()).collect()
+}
+
+// indices picked by size evenly spaced pointers over the cumulative weights,
+// starting from a single random offset. an index with weight w is picked
+// either floor or ceil of size * w / total times
+pub fn stochastic_universal_sampling<R: Rng + ?Sized>(
+    rng: &mut R,
+    weights: &[f64],
+    size: usize,
+) -> Vec<usize> {
+    let total: f64 = weights.iter().sum();
+    let step = total / size as f64;
+    let start = rng.random_range(0.0..step);
+
+    let mut picked = Vec::with_capacity(size);
+    let mut index = 0;
+    let mut cumulative = weights[0];
+    for i in 0..size {
+        let pointer = start + i as f64 * step;
+        while cumulative <= pointer && index + 1 < weights.len() {
+            index += 1;
+            cumulative += weights[index];
+        }
+        picked.push(index);
+    }
+    picked
+}
+
+// where single cut crossovers split the parents; Fixed and MidPoint make
+// the children predictable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutStrategy {
+    Random,
+    Fixed(usize),
+    MidPoint,
+}
+
+impl CutStrategy {
+    pub fn cut_point<R: Rng + ?Sized>(&self, rng: &mut R, length: usize) -> usize {
+        match self {
+            CutStrategy::Random => rng.random_range(0..length),
+            CutStrategy::Fixed(cut) => (*cut).min(length),
+            CutStrategy::MidPoint => length / 2,
+        }
+    }
+}
+
+// random, mid, or the index of a fixed cut
+impl std::str::FromStr for CutStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "random" => Ok(CutStrategy::Random),
+            "mid" => Ok(CutStrategy::MidPoint),
+            _ => s
+                .parse()
+                .map(CutStrategy::Fixed)
+                .map_err(|_| format!("unknown cut {s}, expected random, mid or an index")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sense {
+    Minimize,
+    Maximize,
+}
+
+impl Sense {
+    // orders fitness values best first
+    pub fn compare(&self, a: f64, b: f64) -> std::cmp::Ordering {
+        match self {
+            Sense::Minimize => a.total_cmp(&b),
+            Sense::Maximize => b.total_cmp(&a),
+        }
+    }
+
+    pub fn is_better(&self, a: f64, b: f64) -> bool {
+        self.compare(a, b).is_lt()
+    }
+
+    // how far fitness is ahead of worst, never negative for a sorted population
+    pub fn margin(&self, fitness: f64, worst: f64) -> f64 {
+        match self {
+            Sense::Minimize => worst - fitness,
+            Sense::Maximize => fitness - worst,
+        }
+    }
+
+    // percentage by which best falls short of a known optimum, 0 when equal
+    pub fn optimality_gap(&self, best: f64, optimum: f64) -> f64 {
+        100.0 * self.margin(optimum, best) / optimum.abs()
+    }
+}
+
+pub trait Individual: Fitness<f64> {
+    const SENSE: Sense;
+}
+
+// a candidate solution the shared evolution loop can work with
+pub trait Genome: Individual + Mutate + Ord + Clone {
+    type Gene: PartialEq;
+
+    fn genes(&self) -> &[Self::Gene];
+    // a new random genome for the same problem instance
+    fn random<R: Rng + ?Sized>(&self, rng: &mut R) -> Self;
+    // panics naming the operator if it produced an invalid genome
+    fn validate(&self, operator: &str);
+    // whether the cached fitness matches one recomputed from scratch, false
+    // when something changed the genome without clearing the cache
+    fn verify(&self) -> bool;
+}
+
+pub trait Termination {
+    fn should_stop(&self, generation: usize, best_fitness: f64, stagnation: usize) -> bool;
+
+    // whether stopping at best_fitness counts as having met a fitness target
+    fn reached_target(&self, _best_fitness: f64) -> bool {
+        false
+    }
+}
+
+// stops once generation reaches the limit
+pub struct GenerationLimit(pub usize);
+
+impl Termination for GenerationLimit {
+    fn should_stop(&self, generation: usize, _best_fitness: f64, _stagnation: usize) -> bool {
+        generation >= self.0
+    }
+}
+
+// stops once the best fitness is at least as good as target
+pub struct TargetFitness {
+    pub target: f64,
+    pub sense: Sense,
+}
+
+impl Termination for TargetFitness {
+    fn should_stop(&self, _generation: usize, best_fitness: f64, _stagnation: usize) -> bool {
+        self.reached_target(best_fitness)
+    }
+
+    fn reached_target(&self, best_fitness: f64) -> bool {
+        !self.sense.is_better(self.target, best_fitness)
+    }
+}
+
+// stops after this many generations without improvement
+pub struct StagnationLimit(pub usize);
+
+impl Termination for StagnationLimit {
+    fn should_stop(&self, _generation: usize, _best_fitness: f64, stagnation: usize) -> bool {
+        stagnation >= self.0
+    }
+}
+
+pub struct And<A, B>(pub A, pub B);
+
+impl<A: Termination, B: Termination> Termination for And<A, B> {
+    fn should_stop(&self, generation: usize, best_fitness: f64, stagnation: usize) -> bool {
+        self.0.should_stop(generation, best_fitness, stagnation)
+            && self.1.should_stop(generation, best_fitness, stagnation)
+    }
+
+    fn reached_target(&self, best_fitness: f64) -> bool {
+        self.0.reached_target(best_fitness) || self.1.reached_target(best_fitness)
+    }
+}
+
+pub struct Or<A, B>(pub A, pub B);
+
+impl<A: Termination, B: Termination> Termination for Or<A, B> {
+    fn should_stop(&self, generation: usize, best_fitness: f64, stagnation: usize) -> bool {
+        self.0.should_stop(generation, best_fitness, stagnation)
+            || self.1.should_stop(generation, best_fitness, stagnation)
+    }
+
+    fn reached_target(&self, best_fitness: f64) -> bool {
+        self.0.reached_target(best_fitness) || self.1.reached_target(best_fitness)
+    }
+}
+
+pub struct Not<T>(pub T);
+
+impl<T: Termination> Termination for Not<T> {
+    fn should_stop(&self, generation: usize, best_fitness: f64, stagnation: usize) -> bool {
+        !self.0.should_stop(generation, best_fitness, stagnation)
+    }
+}
+
+// temperature for Boltzmann selection in fitness units, starting at initial
+// and multiplied by rate every generation but never dropping below min. high
+// temperatures select almost uniformly, low ones almost only the best
+#[derive(Debug, Clone, Copy)]
+pub struct CoolingSchedule {
+    pub initial: f64,
+    pub rate: f64,
+    pub min: f64,
+}
+
+impl CoolingSchedule {
+    pub fn temperature(&self, generation: usize) -> f64 {
+        (self.initial * self.rate.powi(generation as i32)).max(self.min)
+    }
+}
+
+// selection weight of a genome with the given fitness at temperature. the gap
+// to the best keeps exp from overflowing, and the floor keeps every weight
+// positive so weighted sampling can always draw enough genomes
+pub fn boltzmann_weight(sense: Sense, fitness: f64, best: f64, temperature: f64) -> f64 {
+    (-sense.margin(best, fitness) / temperature)
+        .exp()
+        .max(f64::MIN_POSITIVE)
+}
+
+// population mean and standard deviation of values
+pub fn mean_std_dev(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+// sigma scaled selection weight, 1 plus how many half standard deviations
+// fitness is better than the mean. this keeps the pressure the same whether
+// the fitness values are spread out or bunched together. floored like
+// boltzmann_weight, and every genome weighs 1 when they are all equal
+pub fn sigma_weight(sense: Sense, fitness: f64, mean: f64, std_dev: f64) -> f64 {
+    if std_dev == 0.0 {
+        return 1.0;
+    }
+    (1.0 + sense.margin(fitness, mean) / (2.0 * std_dev)).max(f64::MIN_POSITIVE)
+}
+
+// whether a is at least as good as b on every objective and better on one
+pub fn dominates(senses: &[Sense], a: &[f64], b: &[f64]) -> bool {
+    let mut better = false;
+    for ((sense, x), y) in senses.iter().zip(a).zip(b) {
+        if sense.is_better(*y, *x) {
+            return false;
+        }
+        better |= sense.is_better(*x, *y);
+    }
+    better
+}
+
+// indices of points grouped into pareto fronts, the non-dominated ones first
+// and each later front only dominated by points in earlier ones
+pub fn non_dominated_sort<P: AsRef<[f64]>>(senses: &[Sense], points: &[P]) -> Vec<Vec<usize>> {
+    let n = points.len();
+    let mut dominated_by = vec![0; n];
+    let mut dominated: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let (a, b) = (points[i].as_ref(), points[j].as_ref());
+            if dominates(senses, a, b) {
+                dominated[i].push(j);
+                dominated_by[j] += 1;
+            } else if dominates(senses, b, a) {
+                dominated[j].push(i);
+                dominated_by[i] += 1;
+            }
+        }
+    }
+
+    let mut fronts = Vec::new();
+    let mut front: Vec<usize> = (0..n).filter(|i| dominated_by[*i] == 0).collect();
+    while !front.is_empty() {
+        let mut next = Vec::new();
+        for i in &front {
+            for j in &dominated[*i] {
+                dominated_by[*j] -= 1;
+                if dominated_by[*j] == 0 {
+                    next.push(*j);
+                }
+            }
+        }
+        fronts.push(front);
+        front = next;
+    }
+    fronts
+}
+
+// for each point of front, the normalised size of the gap its neighbours
+// leave on every objective. the ends of a front are always kept, so they get
+// infinity
+pub fn crowding_distance<P: AsRef<[f64]>>(points: &[P], front: &[usize]) -> Vec<f64> {
+    let n = front.len();
+    if n <= 2 {
+        return vec![f64::INFINITY; n];
+    }
+
+    let mut distance = vec![0.0; n];
+    let objectives = points[front[0]].as_ref().len();
+    for k in 0..objectives {
+        let value = |i: usize| points[front[i]].as_ref()[k];
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|a, b| value(*a).total_cmp(&value(*b)));
+
+        let (low, high) = (value(order[0]), value(order[n - 1]));
+        distance[order[0]] = f64::INFINITY;
+        distance[order[n - 1]] = f64::INFINITY;
+        if high > low {
+            for w in 1..n - 1 {
+                distance[order[w]] += (value(order[w + 1]) - value(order[w - 1])) / (high - low);
+            }
+        }
+    }
+    distance
+}
+
+// nsga-ii order on (front, crowding distance): the earlier front first, then
+// the less crowded point
+pub fn crowded_compare(a: (usize, f64), b: (usize, f64)) -> std::cmp::Ordering {
+    a.0.cmp(&b.0).then(b.1.total_cmp(&a.1))
+}
+
+// Generational breeds a whole new population every generation, SteadyState
+// only breeds replacements offspring per step and swaps them for the worst
+// genomes, leaving the rest of the population in place
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvolutionMode {
+    Generational,
+    SteadyState { replacements: usize },
+}
+
+// generational, or steady-state:N to replace N genomes per step
+impl std::str::FromStr for EvolutionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.split_once(':') {
+            Some(("steady-state", n)) => n
+                .parse()
+                .map(|replacements| EvolutionMode::SteadyState { replacements })
+                .map_err(|_| format!("invalid replacement count {n}")),
+            _ if s == "generational" => Ok(EvolutionMode::Generational),
+            _ => Err(format!(
+                "unknown mode {s}, expected generational or steady-state:N"
+            )),
+        }
+    }
+}
+
+// tracks the best fitness so far and how many generations in a row failed to
+// beat it by more than tolerance, converged once that count passes threshold
+#[derive(Debug, Clone, Copy)]
+pub struct ConvergenceDetector {
+    pub sense: Sense,
+    pub threshold: usize,
+    pub tolerance: f64,
+    pub best: f64,
+    pub stagnant: usize,
+}
+
+impl ConvergenceDetector {
+    pub fn new(sense: Sense, threshold: usize, tolerance: f64) -> Self {
+        let best = match sense {
+            Sense::Minimize => f64::MAX,
+            Sense::Maximize => f64::MIN,
+        };
+        Self {
+            sense,
+            threshold,
+            tolerance,
+            best,
+            stagnant: 0,
+        }
+    }
+
+    // records the best fitness of a generation, returning whether it counts
+    // as an improvement
+    pub fn update(&mut self, fitness: f64) -> bool {
+        if self.sense.margin(fitness, self.best) > self.tolerance {
+            self.best = fitness;
+            self.stagnant = 0;
+            true
+        } else {
+            self.stagnant += 1;
+            false
+        }
+    }
+
+    pub fn converged(&self) -> bool {
+        self.stagnant > self.threshold
+    }
+
+    // starts counting stagnation again, e.g. after a restart
+    pub fn reset(&mut self) {
+        self.stagnant = 0;
+    }
+}
+
+// tuning knobs shared by both drivers
+#[derive(Debug, Clone)]
+pub struct EvolutionConfig {
+    // swaps or flips per offspring and the chance each one happens
+    pub mutation_n: usize,
+    pub mutation_prob: f64,
+    // best genomes carried over unchanged into the next generation
+    pub elite_count: usize,
+    // generations without improvement before the population counts as converged
+    pub stagnation_limit: usize,
+    // smallest change in the best fitness that counts as an improvement
+    pub improvement_tolerance: f64,
+    // on convergence keep this many of the best genomes and regenerate the
+    // rest, None leaves a converged population alone
+    pub reset_retain: Option<usize>,
+    // chance a pair of parents is crossed over rather than only mutated
+    pub crossover_rate: f64,
+    // raise mutation_prob while the population is stuck. only mutations that
+    // read the probability respond, for knapsack that is MutationMode::PerGene
+    pub adaptive_mutation: Option<AdaptiveMutation>,
+    pub mode: EvolutionMode,
+    // only used by Boltzmann selection
+    pub cooling: CoolingSchedule,
+    pub selection: SelectionStrategy,
+    pub replacement: ReplacementStrategy,
+    // let offspring displace survivors of equal fitness (neutral drift)
+    pub accept_equal_offspring: bool,
+    pub cut_strategy: CutStrategy,
+    // check every genome right after each crossover and mutation
+    pub safe_mode: bool,
+    // stop once this many genomes have been evaluated
+    pub max_evaluations: Option<usize>,
+    // keep each new best in RunResult::best_genomes
+    pub record_best_genomes: bool,
+    // tally how often each operator beat its input. this evaluates crossed
+    // children before they are mutated, two extra evaluations per crossover
+    pub operator_stats: bool,
+}
+
+impl EvolutionConfig {
+    // mutation probability for a generation after stagnation generations
+    // without improvement
+    pub fn mutation_prob_at(&self, stagnation: usize) -> f64 {
+        match self.adaptive_mutation {
+            Some(adaptive) => adaptive.probability(self.mutation_prob, stagnation),
+            None => self.mutation_prob,
+        }
+    }
+}
+
+impl Default for EvolutionConfig {
+    fn default() -> Self {
+        Self {
+            mutation_n: 1,
+            mutation_prob: 0.5,
+            elite_count: 2,
+            stagnation_limit: 50,
+            improvement_tolerance: 0.0,
+            reset_retain: Some(5),
+            crossover_rate: 1.0,
+            adaptive_mutation: None,
+            mode: EvolutionMode::Generational,
+            cooling: CoolingSchedule {
+                initial: 100.0,
+                rate: 0.99,
+                min: 0.01,
+            },
+            selection: SelectionStrategy::Roulette,
+            replacement: ReplacementStrategy::Plus,
+            accept_equal_offspring: false,
+            cut_strategy: CutStrategy::Random,
+            safe_mode: false,
+            max_evaluations: None,
+            record_best_genomes: false,
+            operator_stats: false,
+        }
+    }
+}
+
+// command line flags for the EvolutionConfig options both problems share,
+// each left as the problem's default when not given
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct RunFlags {
+    // roulette, tournament:K, rank, sus, boltzmann or sigma
+    #[arg(long)]
+    pub selection: Option<SelectionStrategy>,
+    // plus keeps the elites, comma replaces every genome
+    #[arg(long)]
+    pub replacement: Option<ReplacementStrategy>,
+    // generational, or steady-state:N to replace N genomes per step
+    #[arg(long)]
+    pub mode: Option<EvolutionMode>,
+    // where single cut crossovers split: random, mid or an index
+    #[arg(long)]
+    pub cut: Option<CutStrategy>,
+    // let offspring displace survivors of equal fitness
+    #[arg(long)]
+    pub accept_equal: bool,
+    // check every genome after each crossover and mutation
+    #[arg(long)]
+    pub safe_mode: bool,
+    // stop once this many genomes have been evaluated
+    #[arg(long)]
+    pub max_evaluations: Option<usize>,
+}
+
+impl RunFlags {
+    pub fn apply(&self, config: &mut EvolutionConfig) {
+        if let Some(selection) = self.selection {
+            config.selection = selection;
+        }
+        if let Some(replacement) = self.replacement {
+            config.replacement = replacement;
+        }
+        if let Some(mode) = self.mode {
+            config.mode = mode;
+        }
+        if let Some(cut) = self.cut {
+            config.cut_strategy = cut;
+        }
+        config.accept_equal_offspring |= self.accept_equal;
+        config.safe_mode |= self.safe_mode;
+        if self.max_evaluations.is_some() {
+            config.max_evaluations = self.max_evaluations;
+        }
+    }
+}
+
+// moves the mutation probability linearly from the base rate to max_prob over
+// ramp generations of stagnation, falling back to the base once a new best is
+// found. it changes the prob passed to Mutate, not the count n
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveMutation {
+    pub max_prob: f64,
+    pub ramp: usize,
+}
+
+impl AdaptiveMutation {
+    pub fn probability(&self, base: f64, stagnation: usize) -> f64 {
+        let progress = (stagnation as f64 / self.ramp.max(1) as f64).min(1.0);
+        base + (self.max_prob - base) * progress
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OperatorCount {
+    pub applied: usize,
+    pub improved: usize,
+}
+
+// tallies how often each operator fired and how often it beat its input
+#[derive(Debug, Clone, Default)]
+pub struct OperatorStats {
+    pub counts: std::collections::BTreeMap<String, OperatorCount>,
+}
+
+impl OperatorStats {
+    pub fn record(&mut self, operator: &str, improved: bool) {
+        let count = self.counts.entry(operator.to_string()).or_default();
+        count.applied += 1;
+        if improved {
+            count.improved += 1;
+        }
+    }
+}
+
+impl std::fmt::Display for OperatorStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (operator, count) in &self.counts {
+            writeln!(
+                f,
+                "{operator}: applied {} | improved {}",
+                count.applied, count.improved
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RunMetrics {
+    pub operator_stats: OperatorStats,
+    pub evaluations: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunResult<G, T> {
+    pub best: G,
+    pub fitness: T,
+    pub generations: usize,
+    pub met_target: bool,
+    pub metrics: RunMetrics,
+    // each new best in the order it was found, when recording is enabled
+    pub best_genomes: Vec<G>,
+    // best fitness of generations 0..=generations, so it holds generations + 1
+    // values and the last one is fitness
+    pub history: Vec<T>,
+}
+
+// everything run_evolution tracks about a population besides its genomes
+#[derive(Debug, Clone)]
+pub struct RunState<G> {
+    pub convergence: ConvergenceDetector,
+    pub operator_stats: OperatorStats,
+    // genomes created so far, each of which needs its fitness evaluated
+    pub evaluations: usize,
+    // each new best in the order it was found, when recording is enabled
+    pub best_genomes: Vec<G>,
+    // for Boltzmann selection, set by run_evolution every generation
+    pub temperature: f64,
+    // best fitness of each generation so far
+    pub history: Vec<f64>,
+}
+
+impl<G: Genome> RunState<G> {
+    // evaluations starts at the size of the initial population
+    pub fn new(evaluations: usize) -> Self {
+        Self {
+            convergence: ConvergenceDetector::new(G::SENSE, 50, 0.0),
+            operator_stats: OperatorStats::default(),
+            evaluations,
+            best_genomes: Vec::new(),
+            temperature: 1.0,
+            history: Vec::new(),
+        }
+    }
+}
+
+// what run_evolution needs from a population. the hooks default to doing nothing
+pub trait Evolve: Sized {
+    type Genome: Genome + Sync;
+    type Pair<'a>: Crossover + Mutate;
+
+    fn genomes(&self) -> &[Self::Genome];
+    fn genomes_mut(&mut self) -> &mut Vec<Self::Genome>;
+    fn state(&self) -> &RunState<Self::Genome>;
+    fn state_mut(&mut self) -> &mut RunState<Self::Genome>;
+    // names of the crossover and mutation the next pair will use
+    fn operator_names(&self) -> (String, String);
+    fn pair<'a>(
+        &self,
+        a: &'a mut Self::Genome,
+        b: &'a mut Self::Genome,
+        cut: CutStrategy,
+    ) -> Self::Pair<'a>;
+
+    // fill every fitness cache up front so the sort that follows is cheap
+    fn evaluate(&self) {
+        evaluate_parallel(self.genomes());
+    }
+
+    fn push_offspring(&mut self, genome: Self::Genome) {
+        self.genomes_mut().push(genome);
+        self.state_mut().evaluations += 1;
+    }
+
+    fn evaluations_exhausted(&self, config: &EvolutionConfig) -> bool {
+        config
+            .max_evaluations
+            .is_some_and(|max| self.state().evaluations >= max)
+    }
+
+    // the first genome is the best once the population is sorted
+    fn result(&self, generations: usize, met_target: bool) -> RunResult<Self::Genome, f64> {
+        let best = self.genomes().first().unwrap().clone();
+        let state = self.state();
+        RunResult {
+            fitness: best.fitness(),
+            best,
+            generations,
+            met_target,
+            metrics: RunMetrics {
+                operator_stats: state.operator_stats.clone(),
+                evaluations: state.evaluations,
+            },
+            best_genomes: state.best_genomes.clone(),
+            history: state.history.clone(),
+        }
+    }
+
+    fn elite_count(&self, config: &EvolutionConfig) -> usize {
+        config.elite_count
+    }
+
+    // parents are drawn from this many of the best genomes, all of them by default
+    fn breeding_pool(&self) -> usize {
+        self.genomes().len()
+    }
+
+    // keeps the best retain genomes, at least the best one so the convergence
+    // best is still in the population, and regenerates the rest
+    fn reset_keep_best<R: Rng + ?Sized>(&mut self, rng: &mut R, retain: usize) {
+        self.state_mut().convergence.reset();
+        self.genomes_mut().sort();
+        let size = self.genomes().len();
+        let retain = retain.clamp(1, size);
+        let best = &self.genomes()[0];
+        let mut new: Vec<Self::Genome> = (retain..size).map(|_| best.random(rng)).collect();
+        self.state_mut().evaluations += new.len();
+        self.genomes_mut().truncate(retain);
+        self.genomes_mut().append(&mut new);
+        self.genomes_mut().sort();
+    }
+
+    // runs on the sorted population before the best is checked
+    fn before_generation<R: Rng + ?Sized>(&mut self, _generation: usize, _rng: &mut R) {}
+
+    // runs once a generation has been checked against termination, before breeding
+    fn after_generation<R: Rng + ?Sized>(
+        &mut self,
+        _generation: usize,
+        _config: &EvolutionConfig,
+        _rng: &mut R,
+    ) {
+    }
+}
+
+// told about every generation once its best has been checked, improved is
+// true when that generation found a new best
+pub trait Observer<P> {
+    fn on_generation(&mut self, generation: usize, population: &P, improved: bool);
+}
+
+impl<P, F: FnMut(usize, &P, bool)> Observer<P> for F {
+    fn on_generation(&mut self, generation: usize, population: &P, improved: bool) {
+        self(generation, population, improved)
+    }
+}
+
+// evolves population until termination or max_evaluations stops it
+pub fn run_evolution<P: Evolve, R: Rng + ?Sized>(
+    population: &mut P,
+    config: &EvolutionConfig,
+    termination: &dyn Termination,
+    observer: &mut dyn Observer<P>,
+    rng: &mut R,
+) -> RunResult<P::Genome, f64> {
+    start_run(population, config);
+    (0..)
+        .find_map(|generation| {
+            step_generation(population, config, termination, observer, generation, rng)
+        })
+        .unwrap()
+}
+
+// run_evolution one generation per step() for callers that drive the run
+// themselves, such as an interactive viewer
+pub struct GaRunner<P, R> {
+    population: P,
+    config: EvolutionConfig,
+    termination: Box<dyn Termination>,
+    rng: R,
+    generation: usize,
+}
+
+impl<P: Evolve, R: Rng> GaRunner<P, R> {
+    pub fn new(
+        mut population: P,
+        config: EvolutionConfig,
+        termination: Box<dyn Termination>,
+        rng: R,
+    ) -> Self {
+        start_run(&mut population, &config);
+        Self {
+            population,
+            config,
+            termination,
+            rng,
+            generation: 0,
+        }
+    }
+
+    pub fn population(&self) -> &P {
+        &self.population
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    // runs one generation, returning the result once the run is over
+    pub fn step(&mut self, observer: &mut dyn Observer<P>) -> Option<RunResult<P::Genome, f64>> {
+        let result = step_generation(
+            &mut self.population,
+            &self.config,
+            self.termination.as_ref(),
+            observer,
+            self.generation,
+            &mut self.rng,
+        );
+        self.generation += 1;
+        result
+    }
+}
+
+fn start_run<P: Evolve>(population: &mut P, config: &EvolutionConfig) {
+    let convergence = &mut population.state_mut().convergence;
+    convergence.sense = P::Genome::SENSE;
+    convergence.threshold = config.stagnation_limit;
+    convergence.tolerance = config.improvement_tolerance;
+}
+
+// one generation of run_evolution, Some once termination or the evaluation
+// budget ends the run
+fn step_generation<P: Evolve, R: Rng + ?Sized>(
+    population: &mut P,
+    config: &EvolutionConfig,
+    termination: &dyn Termination,
+    observer: &mut dyn Observer<P>,
+    generation: usize,
+    rng: &mut R,
+) -> Option<RunResult<P::Genome, f64>> {
+    population.evaluate();
+    population.genomes_mut().sort();
+    population.before_generation(generation, rng);
+
+    let best = population.genomes().first().unwrap().fitness();
+    let improved = population.state_mut().convergence.update(best);
+    if improved && config.record_best_genomes {
+        let genome = population.genomes().first().unwrap().clone();
+        population.state_mut().best_genomes.push(genome);
+    }
+    observer.on_generation(generation, population, improved);
+
+    population.state_mut().history.push(best);
+    let stagnant = population.state().convergence.stagnant;
+    if termination.should_stop(generation, best, stagnant) {
+        return Some(population.result(generation, termination.reached_target(best)));
+    }
+    population.state_mut().temperature = config.cooling.temperature(generation);
+    population.after_generation(generation, config, rng);
+
+    // a spent budget would leave a Comma population with no genomes at all
+    if population.evaluations_exhausted(config) {
+        population.genomes_mut().sort();
+        return Some(population.result(generation, false));
+    }
+
+    let size = population.genomes().len();
+    let mutation_prob = config.mutation_prob_at(population.state().convergence.stagnant);
+    match config.mode {
+        EvolutionMode::Generational => {
+            // under (mu,lambda) the elites are not carried over either
+            let elites = match config.replacement {
+                ReplacementStrategy::Plus => population.elite_count(config).min(size),
+                ReplacementStrategy::Comma => 0,
+            };
+            // the population keeps only its distinct elites and refills
+            // with offspring bred from the genomes it had before
+            let survivors = unique_elites(population.genomes(), elites);
+            let kept = survivors.len();
+            let pool = population.breeding_pool().clamp(1, size);
+            let mut parents = std::mem::replace(population.genomes_mut(), survivors);
+            parents.truncate(pool);
+
+            while population.genomes().len() < size {
+                if population.evaluations_exhausted(config) {
+                    break;
+                }
+
+                let (a, b, tally) = breed(population, &parents, config, mutation_prob, rng);
+                tally.record(population.state_mut());
+                population.push_offspring(a);
+                if population.genomes().len() < size {
+                    population.push_offspring(b);
+                }
+            }
+
+            // the next sort is stable, so whichever comes first wins a fitness tie
+            if config.accept_equal_offspring {
+                population.genomes_mut().rotate_left(kept);
+            }
+        }
+        EvolutionMode::SteadyState { replacements } => {
+            let replacements = replacements.min(size);
+            let mut offspring = Vec::with_capacity(replacements);
+            while offspring.len() < replacements && !population.evaluations_exhausted(config) {
+                let pool = population.breeding_pool().clamp(1, size);
+                let parents = &population.genomes()[..pool];
+                let (a, b, tally) = breed(population, parents, config, mutation_prob, rng);
+                tally.record(population.state_mut());
+                offspring.push(a);
+                if offspring.len() < replacements {
+                    offspring.push(b);
+                }
+            }
+
+            // the population is still sorted, so the worst are at the end
+            population.genomes_mut().truncate(size - offspring.len());
+            for genome in offspring {
+                population.push_offspring(genome);
+            }
+        }
+    }
+
+    if population.evaluations_exhausted(config) {
+        population.evaluate();
+        population.genomes_mut().sort();
+        let best = population.genomes().first().unwrap().fitness();
+        population.state_mut().history.push(best);
+        return Some(population.result(generation + 1, false));
+    }
+
+    None
+}
+
+// summary of the final fitness over repeated runs of the same setup
+#[derive(Debug, Clone, Copy)]
+pub struct RunStats {
+    pub runs: usize,
+    pub best: f64,
+    pub worst: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    // mean generations of the runs that met the target, None if none did
+    pub mean_generations_to_target: Option<f64>,
+}
+
+// runs the evolution once per seed in seed..seed + runs, building a fresh
+// population for each, so operators can be compared on more than one run
+pub fn run_many<P: Evolve, F: FnMut(&mut StdRng) -> P>(
+    mut make_population: F,
+    config: &EvolutionConfig,
+    termination: &dyn Termination,
+    runs: usize,
+    seed: u64,
+) -> RunStats {
+    assert!(runs > 0, "run_many needs at least one run");
+    let sense = P::Genome::SENSE;
+
+    let mut fitnesses = Vec::with_capacity(runs);
+    let mut target_generations = Vec::new();
+    for run in 0..runs {
+        let mut rng = StdRng::seed_from_u64(seed.wrapping_add(run as u64));
+        let mut population = make_population(&mut rng);
+        let result = run_evolution(
+            &mut population,
+            config,
+            termination,
+            &mut |_: usize, _: &P, _: bool| {},
+            &mut rng,
+        );
+        fitnesses.push(result.fitness);
+        if result.met_target {
+            target_generations.push(result.generations as f64);
+        }
+    }
+
+    let best = fitnesses
+        .iter()
+        .copied()
+        .reduce(|a, b| if sense.is_better(b, a) { b } else { a })
+        .unwrap();
+    let worst = fitnesses
+        .iter()
+        .copied()
+        .reduce(|a, b| if sense.is_better(a, b) { b } else { a })
+        .unwrap();
+    let (mean, std_dev) = mean_std_dev(&fitnesses);
+    let mean_generations_to_target = (!target_generations.is_empty())
+        .then(|| target_generations.iter().sum::<f64>() / target_generations.len() as f64);
+
+    RunStats {
+        runs,
+        best,
+        worst,
+        mean,
+        std_dev,
+        mean_generations_to_target,
+    }
+}
+
+// whether each operator breed applied beat its input, and the evaluations it
+// took to find out. empty unless config.operator_stats is set
+#[derive(Default)]
+struct Tally {
+    operators: Vec<(String, bool)>,
+    evaluations: usize,
+}
+
+impl Tally {
+    fn record<G>(self, state: &mut RunState<G>) {
+        for (operator, improved) in self.operators {
+            state.operator_stats.record(&operator, improved);
+        }
+        state.evaluations += self.evaluations;
+    }
+}
+
+// two offspring from a pair of parents selected from genomes
+fn breed<P: Evolve, R: Rng + ?Sized>(
+    population: &P,
+    genomes: &[P::Genome],
+    config: &EvolutionConfig,
+    mutation_prob: f64,
+    rng: &mut R,
+) -> (P::Genome, P::Genome, Tally) {
+    let sense = P::Genome::SENSE;
+    let mut parents = select(
+        genomes,
+        config.selection,
+        population.state().temperature,
+        rng,
+        2,
+    );
+    let mut b = parents.pop().unwrap();
+    let mut a = parents.pop().unwrap();
+    let mut tally = Tally::default();
+    // the parents' fitness is already cached, so this costs nothing
+    let parent_best = config
+        .operator_stats
+        .then(|| std::cmp::min(&a, &b).fitness());
+
+    let crossed = rng.random_bool(config.crossover_rate);
+    if crossed {
+        population
+            .pair(&mut a, &mut b, config.cut_strategy)
+            .crossover(rng);
+        if config.safe_mode {
+            a.validate("crossover");
+            b.validate("crossover");
+        }
+    }
+    let crossed_best = parent_best.map(|parent_best| {
+        if !crossed {
+            return parent_best;
+        }
+        let crossed_best = std::cmp::min(&a, &b).fitness();
+        let (crossover, _) = population.operator_names();
+        tally
+            .operators
+            .push((crossover, sense.is_better(crossed_best, parent_best)));
+        tally.evaluations += 2;
+        crossed_best
+    });
+
+    population.pair(&mut a, &mut b, config.cut_strategy).mutate(
+        rng,
+        config.mutation_n,
+        mutation_prob,
+    );
+    if config.safe_mode {
+        a.validate("mutation");
+        b.validate("mutation");
+    }
+    if let Some(crossed_best) = crossed_best {
+        // the offspring are evaluated anyway once they join the population
+        let mutated_best = std::cmp::min(&a, &b).fitness();
+        let (_, mutation) = population.operator_names();
+        tally
+            .operators
+            .push((mutation, sense.is_better(mutated_best, crossed_best)));
+    }
+    (a, b, tally)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adaptive_mutation_rises_with_stagnation_up_to_max() {
+        let adaptive = AdaptiveMutation {
+            max_prob: 0.9,
+            ramp: 20,
+        };
+        let probabilities: Vec<f64> = (0..40)
+            .map(|stagnation| adaptive.probability(0.1, stagnation))
+            .collect();
+        assert_eq!(probabilities[0], 0.1);
+        assert!(probabilities.windows(2).all(|w| w[0] <= w[1]));
+        assert!(probabilities[10] > 0.1 && probabilities[10] < 0.9);
+        assert!(probabilities[20..].iter().all(|p| (p - 0.9).abs() < 1e-12));
+    }
+
+    #[test]
+    fn built_in_terminations_stop_at_their_limits() {
+        assert!(GenerationLimit(10).should_stop(10, 0.0, 0));
+        assert!(!GenerationLimit(10).should_stop(9, 0.0, 0));
+        assert!(StagnationLimit(5).should_stop(0, 0.0, 5));
+        assert!(!StagnationLimit(5).should_stop(0, 0.0, 4));
+
+        let target = TargetFitness {
+            target: 100.0,
+            sense: Sense::Minimize,
+        };
+        assert!(target.should_stop(0, 99.0, 0));
+        assert!(!target.should_stop(0, 101.0, 0));
+        assert!(target.reached_target(100.0));
+    }
+
+    #[test]
+    fn termination_combinators() {
+        let target = TargetFitness {
+            target: 100.0,
+            sense: Sense::Minimize,
+        };
+        // or stops on whichever fires first, and still reports the target
+        assert!(Or(GenerationLimit(10), StagnationLimit(5)).should_stop(3, 0.0, 5));
+        assert!(Or(GenerationLimit(10), StagnationLimit(5)).should_stop(10, 0.0, 0));
+        assert!(!Or(GenerationLimit(10), StagnationLimit(5)).should_stop(3, 0.0, 4));
+        assert!(Or(GenerationLimit(10), target).reached_target(99.0));
+
+        assert!(!And(GenerationLimit(10), StagnationLimit(5)).should_stop(3, 0.0, 5));
+        assert!(And(GenerationLimit(10), StagnationLimit(5)).should_stop(10, 0.0, 5));
+        assert!(!Not(GenerationLimit(10)).should_stop(10, 0.0, 0));
+        assert!(Not(GenerationLimit(10)).should_stop(3, 0.0, 0));
+    }
+
+    #[test]
+    fn convergence_detector_counts_generations_without_improvement() {
+        let mut detector = ConvergenceDetector::new(Sense::Maximize, 2, 0.5);
+        assert!(detector.update(10.0));
+        assert!(!detector.update(10.4));
+        assert!(!detector.update(9.0));
+        assert!(!detector.converged());
+        assert!(!detector.update(10.0));
+        assert!(detector.converged());
+        assert!(detector.update(11.0));
+        assert_eq!((detector.best, detector.stagnant), (11.0, 0));
+    }
+
+    #[test]
+    fn stochastic_universal_sampling_picks_in_proportion() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..20 {
+            let picked = stochastic_universal_sampling(&mut rng, &[1.0, 3.0, 0.0, 4.0], 8);
+            let counts: Vec<usize> = (0..4)
+                .map(|i| picked.iter().filter(|index| **index == i).count())
+                .collect();
+            assert_eq!(counts, [1, 3, 0, 4]);
+        }
+    }
+
+    #[test]
+    fn sigma_weight_follows_the_spread() {
+        let (mean, std_dev) = mean_std_dev(&[1.0, 3.0]);
+        assert_eq!((mean, std_dev), (2.0, 1.0));
+        assert_eq!(sigma_weight(Sense::Maximize, 3.0, mean, std_dev), 1.5);
+        assert_eq!(sigma_weight(Sense::Minimize, 3.0, mean, std_dev), 0.5);
+        assert_eq!(sigma_weight(Sense::Maximize, 5.0, 5.0, 0.0), 1.0);
+        assert!(sigma_weight(Sense::Maximize, -100.0, mean, std_dev) > 0.0);
+    }
+
+    #[test]
+    fn boltzmann_weight_favours_the_best_more_when_cold() {
+        let hot = boltzmann_weight(Sense::Minimize, 12.0, 10.0, 100.0);
+        let cold = boltzmann_weight(Sense::Minimize, 12.0, 10.0, 0.1);
+        assert_eq!(boltzmann_weight(Sense::Minimize, 10.0, 10.0, 0.1), 1.0);
+        assert!(hot > 0.9 && cold < 1e-6 && cold > 0.0);
+    }
+
+    #[test]
+    fn non_dominated_sort_splits_fronts() {
+        let senses = [Sense::Maximize, Sense::Minimize];
+        let points = [[10.0, 5.0], [8.0, 2.0], [7.0, 6.0], [10.0, 5.0], [6.0, 1.0]];
+        let fronts = non_dominated_sort(&senses, &points);
+        assert_eq!(fronts, vec![vec![0, 1, 3, 4], vec![2]]);
+
+        let distance = crowding_distance(&points, &fronts[0]);
+        // the duplicate of point 0 and point 4 are the ends on both objectives
+        assert!(distance[0].is_finite() && distance[1].is_finite());
+        assert!(distance[2].is_infinite() && distance[3].is_infinite());
+        assert_eq!(
+            crowded_compare((0, 1.0), (0, f64::INFINITY)),
+            std::cmp::Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn tournament_is_deterministic_for_a_seed() {
+        let genomes: Vec<u32> = (0..20).rev().collect();
+        let winners = |seed| -> Vec<u32> {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..50)
+                .map(|_| *tournament(&genomes, &mut rng, 3))
+                .collect()
+        };
+        assert_eq!(winners(1), winners(1));
+        assert_ne!(winners(1), winners(2));
+        // a tournament over everyone always picks the best
+        assert_eq!(*tournament(&genomes, &mut StdRng::seed_from_u64(1), 20), 0);
+    }
+
+    #[test]
+    fn best_genome_wins_the_most_tournaments() {
+        let genomes: Vec<u32> = (0..10).collect();
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut wins = [0; 10];
+        for _ in 0..2000 {
+            wins[*tournament(&genomes, &mut rng, 3) as usize] += 1;
+        }
+        assert!(wins.windows(2).all(|w| w[0] >= w[1]), "{wins:?}");
+        // the worst of three never wins
+        assert_eq!(wins[8..], [0, 0]);
+    }
+
+    #[test]
+    fn worst_of_an_unsorted_population() {
+        assert_eq!(*worst(&[3, 9, 1, 5]), 9);
+        assert_eq!(*worst(&[7]), 7);
+    }
+
+    #[test]
+    fn each_sense_sorts_its_best_first() {
+        let mut values = vec![3.0, 9.0, 1.0, 5.0];
+        values.sort_by(|a, b| Sense::Minimize.compare(*a, *b));
+        assert_eq!(values, [1.0, 3.0, 5.0, 9.0]);
+        values.sort_by(|a, b| Sense::Maximize.compare(*a, *b));
+        assert_eq!(values, [9.0, 5.0, 3.0, 1.0]);
+        assert!(Sense::Minimize.is_better(1.0, 3.0));
+        assert!(Sense::Maximize.is_better(3.0, 1.0));
+    }
+
+    // 0/1 genes summed and minimized, bred by an operator that breaks the
+    // first child by setting a gene to 2
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Bits(Vec<u32>);
+
+    impl Fitness<f64> for Bits {
+        fn fitness(&self) -> f64 {
+            self.0.iter().sum::<u32>() as f64
+        }
+    }
+
+    impl Individual for Bits {
+        const SENSE: Sense = Sense::Minimize;
+    }
+
+    impl Ord for Bits {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            Self::SENSE.compare(self.fitness(), other.fitness())
+        }
+    }
+
+    impl PartialOrd for Bits {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Mutate for Bits {
+        fn mutate<R: Rng + ?Sized>(&mut self, _rng: &mut R, _n: usize, _prob: f64) {}
+    }
+
+    impl Genome for Bits {
+        type Gene = u32;
+
+        fn genes(&self) -> &[u32] {
+            &self.0
+        }
+
+        fn random<R: Rng + ?Sized>(&self, rng: &mut R) -> Self {
+            Bits((0..self.0.len()).map(|_| rng.random_range(0..=1)).collect())
+        }
+
+        fn validate(&self, operator: &str) {
+            assert!(
+                self.0.iter().all(|gene| *gene <= 1),
+                "{operator} produced a gene above 1: {:?}",
+                self.0
+            );
+        }
+
+        fn verify(&self) -> bool {
+            true
+        }
+    }
+
+    struct BrokenPair<'a> {
+        a: &'a mut Bits,
+    }
+
+    impl Crossover for BrokenPair<'_> {
+        fn crossover<R: Rng + ?Sized>(&mut self, _rng: &mut R) {
+            self.a.0[0] = 2;
+        }
+    }
+
+    impl Mutate for BrokenPair<'_> {
+        fn mutate<R: Rng + ?Sized>(&mut self, _rng: &mut R, _n: usize, _prob: f64) {}
+    }
+
+    struct BrokenPopulation {
+        genomes: Vec<Bits>,
+        state: RunState<Bits>,
+    }
+
+    impl Evolve for BrokenPopulation {
+        type Genome = Bits;
+        type Pair<'a> = BrokenPair<'a>;
+
+        fn genomes(&self) -> &[Bits] {
+            &self.genomes
+        }
+
+        fn genomes_mut(&mut self) -> &mut Vec<Bits> {
+            &mut self.genomes
+        }
+
+        fn state(&self) -> &RunState<Bits> {
+            &self.state
+        }
+
+        fn state_mut(&mut self) -> &mut RunState<Bits> {
+            &mut self.state
+        }
+
+        fn operator_names(&self) -> (String, String) {
+            ("broken crossover".to_string(), "no mutation".to_string())
+        }
+
+        fn pair<'a>(&self, a: &'a mut Bits, _b: &'a mut Bits, _cut: CutStrategy) -> BrokenPair<'a> {
+            BrokenPair { a }
+        }
+    }
+
+    // one generation of the broken operator, every pair crossed
+    fn breed_broken(safe_mode: bool) -> Vec<Bits> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let genomes = (0..6).map(|_| Bits(vec![0; 4]).random(&mut rng)).collect();
+        let mut population = BrokenPopulation {
+            genomes,
+            state: RunState::new(6),
+        };
+        let config = EvolutionConfig {
+            safe_mode,
+            crossover_rate: 1.0,
+            ..Default::default()
+        };
+        run_evolution(
+            &mut population,
+            &config,
+            &GenerationLimit(1),
+            &mut |_: usize, _: &BrokenPopulation, _: bool| {},
+            &mut rng,
+        );
+        population.genomes
+    }
+
+    #[test]
+    fn broken_operator_goes_unnoticed_without_safe_mode() {
+        let genomes = breed_broken(false);
+        assert!(genomes.iter().any(|genome| genome.0[0] == 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "crossover produced a gene above 1")]
+    fn safe_mode_catches_a_broken_operator() {
+        breed_broken(true);
+    }
+
+    #[test]
+    fn optimality_gap_is_zero_at_the_optimum_and_a_percentage_otherwise() {
+        assert_eq!(Sense::Minimize.optimality_gap(564.0, 564.0), 0.0);
+        assert_eq!(Sense::Maximize.optimality_gap(1030.0, 1030.0), 0.0);
+        // a tour 10% longer, a knapsack 25% emptier
+        assert!((Sense::Minimize.optimality_gap(110.0, 100.0) - 10.0).abs() < 1e-9);
+        assert!((Sense::Maximize.optimality_gap(75.0, 100.0) - 25.0).abs() < 1e-9);
+    }
 }
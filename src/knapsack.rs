@@ -1,136 +1,929 @@
-use crate::genetic::{self, Crossover, Fitness, Mutate, Selection};
-use rand::{Rng, rng, seq::IndexedRandom};
+use std::sync::{Arc, OnceLock};
 
-#[derive(Debug, Clone)]
+use crate::genetic::{
+    self, Crossover, CutStrategy, EvolutionConfig, Evolve, Fitness, GenerationLimit, Individual,
+    Mutate, Or, RunFlags, RunState, Sense, TargetFitness, crowded_compare, crowding_distance,
+    non_dominated_sort, run_evolution,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng, rng, seq::SliceRandom};
+
+#[derive(Debug, Clone, serde::Deserialize)]
 struct Thing {
-    _name: String,
+    name: String,
     value: u32,
     weight: u32,
+    volume: u32,
+    // copies of the item that may be packed, 1 for a plain 0/1 knapsack
+    #[serde(default = "one")]
+    max_count: u32,
+    // at most one item per category may be selected
+    #[serde(default)]
+    category: Option<usize>,
+}
+
+fn one() -> u32 {
+    1
 }
 
 impl Thing {
-    fn new(name: &str, value: u32, weight: u32) -> Self {
+    fn new(name: &str, value: u32, weight: u32, volume: u32) -> Self {
         Self {
-            _name: name.to_string(),
+            name: name.to_string(),
             value,
             weight,
+            volume,
+            max_count: 1,
+            category: None,
+        }
+    }
+
+    #[allow(dead_code)]
+    fn with_max_count(name: &str, value: u32, weight: u32, volume: u32, max_count: u32) -> Self {
+        Self {
+            max_count,
+            ..Self::new(name, value, weight, volume)
+        }
+    }
+
+    #[allow(dead_code)]
+    fn with_category(name: &str, value: u32, weight: u32, volume: u32, category: usize) -> Self {
+        Self {
+            category: Some(category),
+            ..Self::new(name, value, weight, volume)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CrossoverKind {
+    SinglePoint,
+    // swaps the genes between two cut points
+    TwoPoint,
+    Uniform,
+}
+
+// how Constrained fitness treats a packing over the limits
+#[derive(Debug, Clone, Copy)]
+pub enum ConstraintStrategy {
+    // count only the items that still fit, skipping the rest
+    Skip,
+    // full value minus rate per unit of weight and volume over the limits,
+    // so infeasible packings rank below feasible ones of similar value
+    Penalty { rate: f64 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FitnessMode {
+    Constrained(ConstraintStrategy),
+    // value_weight * value - weight_penalty * weight, ignoring the limit
+    Scalarized {
+        value_weight: f64,
+        weight_penalty: f64,
+    },
+}
+
+// skip, penalty:RATE, or scalarized:VALUE_WEIGHT:WEIGHT_PENALTY
+impl std::str::FromStr for FitnessMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let number = |value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("invalid number {value}"))
+        };
+        match s.split(':').collect::<Vec<&str>>()[..] {
+            ["skip"] => Ok(FitnessMode::Constrained(ConstraintStrategy::Skip)),
+            ["penalty", rate] => Ok(FitnessMode::Constrained(ConstraintStrategy::Penalty {
+                rate: number(rate)?,
+            })),
+            ["scalarized", value_weight, weight_penalty] => Ok(FitnessMode::Scalarized {
+                value_weight: number(value_weight)?,
+                weight_penalty: number(weight_penalty)?,
+            }),
+            _ => Err(format!(
+                "unknown fitness {s}, expected skip, penalty:RATE or scalarized:VALUE_WEIGHT:WEIGHT_PENALTY"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MutationMode {
+    // step exactly n distinct genes. this ignores the mutation probability, so
+    // mutation_prob and AdaptiveMutation only have an effect under PerGene
+    ExactCount,
+    // step each gene independently with probability prob
+    PerGene,
+}
+
+// exact or per-gene
+impl std::str::FromStr for MutationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "exact" => Ok(MutationMode::ExactCount),
+            "per-gene" => Ok(MutationMode::PerGene),
+            _ => Err(format!(
+                "unknown mutation mode {s}, expected exact or per-gene"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Initialization {
+    // every gene set with probability 0.5
+    Uniform,
+    // genes set in proportion to their value/weight ratio, 0.5 on average
+    RatioBiased,
+}
+
+// uniform or ratio
+impl std::str::FromStr for Initialization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "uniform" => Ok(Initialization::Uniform),
+            "ratio" => Ok(Initialization::RatioBiased),
+            _ => Err(format!(
+                "unknown initialization {s}, expected uniform or ratio"
+            )),
+        }
+    }
+}
+
+impl Initialization {
+    fn densities(&self, things: &[Thing]) -> Vec<f64> {
+        match self {
+            Initialization::Uniform => vec![0.5; things.len()],
+            Initialization::RatioBiased => {
+                let ratios: Vec<f64> = things
+                    .iter()
+                    .map(|thing| thing.value as f64 / thing.weight.max(1) as f64)
+                    .collect();
+                let mean = ratios.iter().sum::<f64>() / ratios.len() as f64;
+                ratios
+                    .iter()
+                    .map(|ratio| (0.5 * ratio / mean).clamp(0.05, 0.95))
+                    .collect()
+            }
         }
     }
 }
 
+// nsga-ii objectives: maximise the value, minimise the weight
+const OBJECTIVE_SENSES: [Sense; 2] = [Sense::Maximize, Sense::Minimize];
+
+// (front, crowding distance) of every genome, see crowded_compare
+fn crowded_ranking(genomes: &[Genome]) -> Vec<(usize, f64)> {
+    let points: Vec<[f64; 2]> = genomes.iter().map(Genome::objectives).collect();
+    let mut ranking = vec![(0, 0.0); genomes.len()];
+    for (rank, front) in non_dominated_sort(&OBJECTIVE_SENSES, &points)
+        .iter()
+        .enumerate()
+    {
+        for (i, distance) in front.iter().zip(crowding_distance(&points, front)) {
+            ranking[*i] = (rank, distance);
+        }
+    }
+    ranking
+}
+
+const CROSSOVER_ENSEMBLE: [CrossoverKind; 3] = [
+    CrossoverKind::SinglePoint,
+    CrossoverKind::TwoPoint,
+    CrossoverKind::Uniform,
+];
+
 #[derive(Debug, Clone)]
 struct Population {
     data: Vec<Genome>,
+    state: RunState<Genome>,
+    active_operator: usize,
+    // move to the next crossover operator whenever the run converges
+    switch_operator_on_stall: bool,
+    inject_mutant_every: Option<usize>,
+    inject_mutations: usize,
+    mutation_mode: MutationMode,
 }
 
 impl Population {
-    fn new(pop_size: u32, things: &[Thing], limit: u32) -> Self {
-        let data = (0..pop_size).map(|_| Genome::new(things, limit)).collect();
+    #[allow(clippy::too_many_arguments)]
+    fn new<R: Rng + ?Sized>(
+        pop_size: u32,
+        things: &[Thing],
+        limit: u32,
+        volume_limit: u32,
+        mode: FitnessMode,
+        forced_items: &[usize],
+        init: Initialization,
+        rng: &mut R,
+    ) -> Self {
+        assert!(limit > 0, "knapsack limit must be greater than zero");
+        if let Some(i) = forced_items.iter().find(|i| **i >= things.len()) {
+            panic!("forced item {i} is out of range for {} items", things.len());
+        }
+        if let Some(lightest) = things.iter().map(|thing| thing.weight).min()
+            && limit < lightest
+        {
+            eprintln!(
+                "warning: knapsack limit {limit} is below the lightest item weight {lightest}, only the empty knapsack is feasible"
+            );
+        }
 
-        Self { data }
+        let densities = init.densities(things);
+        let things: Arc<[Thing]> = things.into();
+        let data = (0..pop_size)
+            .map(|_| {
+                Genome::new_with_rng(
+                    &things,
+                    limit,
+                    volume_limit,
+                    mode,
+                    forced_items,
+                    &densities,
+                    rng,
+                )
+            })
+            .collect();
+
+        Self {
+            data,
+            state: RunState::new(pop_size as usize),
+            active_operator: 0,
+            switch_operator_on_stall: false,
+            inject_mutant_every: None,
+            inject_mutations: 3,
+            mutation_mode: MutationMode::ExactCount,
+        }
+    }
+
+    // hamming distance as a fraction of the genome length, averaged over
+    // every pair of genomes. 0 once all genomes are identical
+    fn diversity(&self) -> f64 {
+        let n = self.data.len();
+        if n < 2 {
+            return 0.0;
+        }
+
+        // while every gene is 0/1 each genome is packed once, and the pairs
+        // are compared by xor and popcount
+        let packed: Option<Vec<Vec<u64>>> = self.data[0]
+            .is_binary()
+            .then(|| self.data.iter().map(Genome::to_bitvec).collect());
+        let length = self.data[0].data.len() as f64;
+        let mut total = 0.0;
+        for i in 0..n {
+            for j in i + 1..n {
+                let distance = match &packed {
+                    Some(packed) => packed_hamming(&packed[i], &packed[j]),
+                    None => self.data[i].hamming(&self.data[j]),
+                };
+                total += distance as f64 / length;
+            }
+        }
+        total / (n * (n - 1) / 2) as f64
+    }
+
+    // per-position entropy of the gene values averaged over positions, 0 once converged
+    fn entropy(&self) -> f64 {
+        let size = self.data.len() as f64;
+        let length = self.data.first().unwrap().data.len();
+        let total: f64 = (0..length)
+            .map(|i| {
+                let mut counts = std::collections::HashMap::new();
+                for genome in &self.data {
+                    *counts.entry(genome.data[i]).or_insert(0) += 1;
+                }
+                counts
+                    .values()
+                    .map(|count| {
+                        let p = *count as f64 / size;
+                        -p * p.log2()
+                    })
+                    .sum::<f64>()
+            })
+            .sum();
+        total / length as f64
     }
-}
 
-impl genetic::Selection for Population {
-    fn selection(&self, size: usize) -> Self {
-        let mut rng = rng();
-        let data: Vec<Genome> = self
+    fn feasible_fraction(&self) -> f64 {
+        let feasible = self
             .data
-            .choose_multiple_weighted(&mut rng, size, |genome| genome.fitness())
-            .unwrap()
-            .cloned()
-            .collect();
-        Self { data }
+            .iter()
+            .filter(|genome| genome.is_feasible())
+            .count();
+        feasible as f64 / self.data.len() as f64
+    }
+
+    fn crossover_kind(&self) -> CrossoverKind {
+        CROSSOVER_ENSEMBLE[self.active_operator]
+    }
+
+    fn switch_operator(&mut self) {
+        self.state.convergence.reset();
+        self.active_operator = (self.active_operator + 1) % CROSSOVER_ENSEMBLE.len();
+    }
+
+    // binary tournament on front then crowding distance
+    fn crowded_tournament<R: Rng + ?Sized>(&self, rng: &mut R, ranking: &[(usize, f64)]) -> Genome {
+        let a = rng.random_range(0..self.data.len());
+        let b = rng.random_range(0..self.data.len());
+        let winner = match crowded_compare(ranking[a], ranking[b]) {
+            std::cmp::Ordering::Greater => b,
+            _ => a,
+        };
+        self.data[winner].clone()
+    }
+
+    // nsga-ii on value against weight, ignoring the limits and the fitness
+    // mode. each generation breeds as many offspring as there are genomes and
+    // keeps the best half of both by front then crowding distance. returns the
+    // distinct packings of the final pareto front, most valuable first
+    #[allow(dead_code)]
+    fn nsga2<R: Rng + ?Sized>(
+        &mut self,
+        config: &EvolutionConfig,
+        generations: usize,
+        rng: &mut R,
+    ) -> Vec<Genome> {
+        let size = self.data.len();
+        for _ in 0..generations {
+            let ranking = crowded_ranking(&self.data);
+            let mut offspring = Vec::with_capacity(size);
+            while offspring.len() < size {
+                let mut a = self.crowded_tournament(rng, &ranking);
+                let mut b = self.crowded_tournament(rng, &ranking);
+                if rng.random_bool(config.crossover_rate) {
+                    self.pair(&mut a, &mut b, config.cut_strategy)
+                        .crossover(rng);
+                }
+                self.pair(&mut a, &mut b, config.cut_strategy).mutate(
+                    rng,
+                    config.mutation_n,
+                    config.mutation_prob,
+                );
+                offspring.push(a);
+                if offspring.len() < size {
+                    offspring.push(b);
+                }
+            }
+            self.state.evaluations += offspring.len();
+            self.data.append(&mut offspring);
+
+            let ranking = crowded_ranking(&self.data);
+            let mut order: Vec<usize> = (0..self.data.len()).collect();
+            order.sort_by(|a, b| crowded_compare(ranking[*a], ranking[*b]));
+            self.data = order[..size]
+                .iter()
+                .map(|i| self.data[*i].clone())
+                .collect();
+        }
+
+        let points: Vec<[f64; 2]> = self.data.iter().map(Genome::objectives).collect();
+        let mut front: Vec<Genome> = Vec::new();
+        for i in &non_dominated_sort(&OBJECTIVE_SENSES, &points)[0] {
+            if !front.iter().any(|genome| genome.data == self.data[*i].data) {
+                front.push(self.data[*i].clone());
+            }
+        }
+        front.sort_by(|a, b| b.objectives()[0].total_cmp(&a.objectives()[0]));
+        front
+    }
+
+    fn inject_best_mutant<R: Rng + ?Sized>(&mut self, rng: &mut R, mutations: usize) {
+        let mut mutant = self.data.first().unwrap().clone();
+        mutant.mutate(rng, mutations, 1.0);
+        *self.data.last_mut().unwrap() = mutant;
+        self.state.evaluations += 1;
+        self.data.sort()
+    }
+}
+
+impl Evolve for Population {
+    type Genome = Genome;
+    type Pair<'a> = Pair<'a>;
+
+    fn genomes(&self) -> &[Genome] {
+        &self.data
+    }
+
+    fn genomes_mut(&mut self) -> &mut Vec<Genome> {
+        &mut self.data
+    }
+
+    fn state(&self) -> &RunState<Genome> {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut RunState<Genome> {
+        &mut self.state
+    }
+
+    fn operator_names(&self) -> (String, String) {
+        (
+            format!("{:?} crossover", self.crossover_kind()),
+            "bit flip mutation".to_string(),
+        )
+    }
+
+    fn pair<'a>(&self, a: &'a mut Genome, b: &'a mut Genome, cut: CutStrategy) -> Pair<'a> {
+        Pair {
+            a,
+            b,
+            cut,
+            kind: self.crossover_kind(),
+            mutation: self.mutation_mode,
+        }
+    }
+
+    fn after_generation<R: Rng + ?Sized>(
+        &mut self,
+        generation: usize,
+        config: &EvolutionConfig,
+        rng: &mut R,
+    ) {
+        // switching operator restarts the stagnation count, so it takes the
+        // place of a reset
+        if self.switch_operator_on_stall && self.state.convergence.converged() {
+            self.switch_operator();
+        }
+        if let Some(retain) = config.reset_retain
+            && self.state.convergence.converged()
+        {
+            self.reset_keep_best(rng, retain);
+        }
+
+        if let Some(every) = self.inject_mutant_every
+            && generation > 0
+            && generation.is_multiple_of(every)
+        {
+            self.inject_best_mutant(rng, self.inject_mutations);
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 struct Genome {
     data: Vec<u32>,
-    things: Vec<Thing>,
+    // shared by every genome, so a clone copies only the counts
+    things: Arc<[Thing]>,
     limit: u32,
+    volume_limit: u32,
+    mode: FitnessMode,
+    // always selected; mutation never flips these and positional crossover
+    // cannot drop them since every parent carries them
+    forced_items: Vec<usize>,
+    // fitness of data, cleared whenever data changes
+    cached_fitness: OnceLock<f64>,
 }
 
 impl Genome {
-    fn new(things: &[Thing], limit: u32) -> Self {
-        let mut rng = rng();
-        let data = (0..things.len()).map(|_| rng.random_range(0..=1)).collect();
+    fn new_with_rng<R: Rng + ?Sized>(
+        things: &Arc<[Thing]>,
+        limit: u32,
+        volume_limit: u32,
+        mode: FitnessMode,
+        forced_items: &[usize],
+        densities: &[f64],
+        rng: &mut R,
+    ) -> Self {
+        // each copy of an item is packed with the item's density
+        let mut data: Vec<u32> = densities
+            .iter()
+            .zip(things.iter())
+            .map(|(density, thing)| {
+                (0..thing.max_count)
+                    .filter(|_| rng.random_bool(*density))
+                    .count() as u32
+            })
+            .collect();
+        forced_items.iter().for_each(|i| data[*i] = data[*i].max(1));
+        let mut genome = Self {
+            data,
+            things: Arc::clone(things),
+            limit,
+            volume_limit,
+            mode,
+            forced_items: forced_items.to_vec(),
+            cached_fitness: OnceLock::new(),
+        };
+        genome.repair_categories();
+        genome
+    }
+
+    #[allow(dead_code)]
+    fn from_bitvec(
+        bits: &[u64],
+        things: &Arc<[Thing]>,
+        limit: u32,
+        volume_limit: u32,
+        mode: FitnessMode,
+        forced_items: &[usize],
+    ) -> Self {
+        let data = (0..things.len())
+            .map(|i| (bits[i / 64] >> (i % 64)) as u32 & 1)
+            .collect();
         Self {
             data,
-            things: things.to_owned(),
+            things: Arc::clone(things),
             limit,
+            volume_limit,
+            mode,
+            forced_items: forced_items.to_vec(),
+            cached_fitness: OnceLock::new(),
         }
     }
-}
 
-impl genetic::Fitness<u32> for Genome {
-    fn fitness(&self) -> u32 {
-        let mut weight = 0;
-        let mut value = 0;
+    // genes packed 64 to a word, lowest bit first. only meaningful while
+    // every item has a max_count of 1
+    fn to_bitvec(&self) -> Vec<u64> {
+        let mut bits = vec![0; self.data.len().div_ceil(64)];
+        for (i, gene) in self.data.iter().enumerate() {
+            bits[i / 64] |= (*gene as u64) << (i % 64);
+        }
+        bits
+    }
+
+    fn is_binary(&self) -> bool {
+        self.things.iter().all(|thing| thing.max_count == 1)
+    }
 
-        self.things
+    // positions holding a different count
+    fn hamming(&self, other: &Self) -> usize {
+        self.data
             .iter()
-            .enumerate()
-            .map_while(|(i, thing)| {
-                if weight + self.data[i] * thing.weight <= self.limit {
-                    weight += self.data[i] * thing.weight;
-                    value += self.data[i] * thing.value;
-                    Some((weight, value))
+            .zip(&other.data)
+            .filter(|(a, b)| a != b)
+            .count()
+    }
+
+    fn free_genes(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.data.len()).filter(|i| !self.forced_items.contains(i))
+    }
+
+    // flips a 0/1 gene, otherwise takes one copy more or less within 0..=max_count
+    fn step_gene<R: Rng + ?Sized>(&mut self, rng: &mut R, i: usize) {
+        let max_count = self.things[i].max_count;
+        self.data[i] = match self.data[i] {
+            0 => 1.min(max_count),
+            count if count >= max_count => count - 1,
+            count if rng.random_bool(0.5) => count + 1,
+            count => count - 1,
+        };
+    }
+
+    // true when stepping gene i would change nothing, or select an item whose
+    // category another selected item holds so repair_categories undoes it
+    fn is_blocked(&self, i: usize) -> bool {
+        let thing = &self.things[i];
+        self.data[i] == 0
+            && (thing.max_count == 0
+                || thing.category.is_some_and(|category| {
+                    (0..self.data.len()).any(|j| {
+                        j != i && self.data[j] > 0 && self.things[j].category == Some(category)
+                    })
+                }))
+    }
+
+    fn mutate_per_gene<R: Rng + ?Sized>(&mut self, rng: &mut R, prob: f64) {
+        let free: Vec<usize> = self.free_genes().collect();
+        for i in free {
+            if rng.random_bool(prob) {
+                self.step_gene(rng, i);
+            }
+        }
+
+        self.repair_categories();
+    }
+
+    // keep only one selected item of each category, with all its copies.
+    // forced items claim their category first, then the first free item wins
+    fn repair_categories(&mut self) {
+        self.invalidate_fitness();
+        let mut used = vec![];
+        for i in self.forced_items.clone() {
+            if let Some(category) = self.things[i].category {
+                used.push(category);
+            }
+        }
+
+        let free: Vec<usize> = self.free_genes().collect();
+        for i in free {
+            if let Some(category) = self.things[i].category
+                && self.data[i] > 0
+            {
+                if used.contains(&category) {
+                    self.data[i] = 0;
                 } else {
-                    None
+                    used.push(category);
                 }
-            })
-            .for_each(|_| {});
+            }
+        }
+    }
+
+    // panics naming the operator if the genome has the wrong length, a count
+    // above the item's max_count, or a dropped forced item
+    fn validate(&self, operator: &str) {
+        assert_eq!(
+            self.data.len(),
+            self.things.len(),
+            "{operator} produced a genome of the wrong length: {:?}",
+            self.data
+        );
+        assert!(
+            self.data
+                .iter()
+                .zip(self.things.iter())
+                .all(|(gene, thing)| *gene <= thing.max_count),
+            "{operator} produced a count above max_count: {:?}",
+            self.data
+        );
+        assert!(
+            self.forced_items.iter().all(|i| self.data[*i] > 0),
+            "{operator} dropped a forced item: {:?}",
+            self.data
+        );
+    }
+
+    // total value, weight and volume of every selected item, ignoring the limits
+    fn totals(&self) -> (u32, u32, u32) {
+        self.data.iter().zip(self.things.iter()).fold(
+            (0, 0, 0),
+            |(value, weight, volume), (gene, thing)| {
+                (
+                    value + gene * thing.value,
+                    weight + gene * thing.weight,
+                    volume + gene * thing.volume,
+                )
+            },
+        )
+    }
+
+    // (value, weight) for nsga2, ignoring the limits
+    fn objectives(&self) -> [f64; 2] {
+        let (value, weight, _) = self.totals();
+        [value as f64, weight as f64]
+    }
+
+    fn is_feasible(&self) -> bool {
+        let (_, weight, volume) = self.totals();
+        weight <= self.limit && volume <= self.volume_limit
+    }
+
+    // packed value under the constraint strategy, or the scalarized score
+    // when that mode is set
+    fn compute_fitness(&self) -> f64 {
+        match self.mode {
+            FitnessMode::Scalarized {
+                value_weight,
+                weight_penalty,
+            } => return self.scalarized_fitness(value_weight, weight_penalty),
+            FitnessMode::Constrained(ConstraintStrategy::Penalty { rate }) => {
+                return self.penalized_fitness(rate);
+            }
+            FitnessMode::Constrained(ConstraintStrategy::Skip) => {}
+        }
 
-        value
+        self.packed_counts()
+            .iter()
+            .zip(self.things.iter())
+            .map(|(count, thing)| (count * thing.value) as f64)
+            .sum()
+    }
+
+    // copies of each item that Skip actually packs. forced items claim their
+    // share of the limits first, then any selected copy that no longer fits by
+    // weight or volume is skipped rather than ending the count
+    fn packed_counts(&self) -> Vec<u32> {
+        let mut weight = 0;
+        let mut volume = 0;
+        let mut packed = vec![0; self.data.len()];
+
+        let free_items = (0..self.things.len()).filter(|i| !self.forced_items.contains(i));
+        for i in self.forced_items.iter().copied().chain(free_items) {
+            let thing = &self.things[i];
+            for _ in 0..self.data[i] {
+                if weight + thing.weight <= self.limit && volume + thing.volume <= self.volume_limit
+                {
+                    weight += thing.weight;
+                    volume += thing.volume;
+                    packed[i] += 1;
+                }
+            }
+        }
+        packed
+    }
+
+    fn invalidate_fitness(&mut self) {
+        self.cached_fitness = OnceLock::new();
+    }
+
+    fn scalarized_fitness(&self, value_weight: f64, weight_penalty: f64) -> f64 {
+        let (value, weight, _) = self.totals();
+        value_weight * value as f64 - weight_penalty * weight as f64
+    }
+
+    fn penalized_fitness(&self, rate: f64) -> f64 {
+        let (value, weight, volume) = self.totals();
+        let excess = weight.saturating_sub(self.limit) + volume.saturating_sub(self.volume_limit);
+        value as f64 - rate * excess as f64
+    }
+
+    // selected items one per line followed by the totals and remaining capacity
+    fn report(&self) -> String {
+        // under Skip only the copies that fit count towards the totals
+        let packed = match self.mode {
+            FitnessMode::Constrained(ConstraintStrategy::Skip) => self.packed_counts(),
+            _ => self.data.clone(),
+        };
+
+        let mut report = String::new();
+        let (mut value, mut weight, mut volume) = (0, 0, 0);
+        for ((gene, count), thing) in self.data.iter().zip(&packed).zip(self.things.iter()) {
+            if *gene > 0 {
+                report += &format!(
+                    "{:<16} count: {:>3}  value: {:>5}  weight: {:>5}  volume: {:>5}",
+                    thing.name, count, thing.value, thing.weight, thing.volume
+                );
+                if gene > count {
+                    report += &format!("  skipped: {}", gene - count);
+                }
+                report += "\n";
+            }
+            value += count * thing.value;
+            weight += count * thing.weight;
+            volume += count * thing.volume;
+        }
+
+        report += &format!(
+            "total value: {value}  total weight: {weight}  remaining capacity: {}\n",
+            self.limit as i64 - weight as i64
+        );
+        report += &format!(
+            "total volume: {volume}  remaining volume: {}\n",
+            self.volume_limit as i64 - volume as i64
+        );
+        report
+    }
+}
+
+impl genetic::Fitness<f64> for Genome {
+    fn fitness(&self) -> f64 {
+        *self.cached_fitness.get_or_init(|| self.compute_fitness())
+    }
+}
+
+// fraction of genes with the same value
+impl genetic::Similarity for Genome {
+    fn similarity(&self, other: &Self) -> f64 {
+        let same = self.data.len() - self.hamming(other);
+        same as f64 / self.data.len() as f64
     }
 }
 
 impl genetic::Mutate for Genome {
-    fn mutate(&mut self, n: usize, prob: f64) {
-        let mut count = 0;
-        let mut rng = rng();
-        while count < n {
-            let i = rng.random_range(0..self.data.len());
-            if let Some(x) = self.data.get_mut(i) {
-                if rng.random_bool(prob) {
-                    *x = (*x + 1) % 2;
-                    count += 1;
-                }
+    // steps exactly n distinct genes whatever prob is, see MutationMode::ExactCount.
+    // blocked genes are passed over so repair never changes a gene beyond the n
+    fn mutate<R: Rng + ?Sized>(&mut self, rng: &mut R, n: usize, _prob: f64) {
+        let mut free: Vec<usize> = self.free_genes().collect();
+        free.shuffle(rng);
+        let mut stepped = 0;
+        for i in free {
+            if stepped == n {
+                break;
+            }
+            if !self.is_blocked(i) {
+                self.step_gene(rng, i);
+                stepped += 1;
             }
         }
+
+        self.repair_categories();
     }
 }
 
 struct Pair<'a> {
     a: &'a mut Genome,
     b: &'a mut Genome,
+    cut: CutStrategy,
+    kind: CrossoverKind,
+    mutation: MutationMode,
 }
 
 impl genetic::Crossover for Pair<'_> {
-    fn crossover(&mut self) {
-        let mut rng = rng();
-        let length = self.a.data.len();
-        let cut_point = rng.random_range(0..length);
-        let a_swap = self.a.data.split_off(cut_point);
-        let b_swap = self.b.data.split_off(cut_point);
-        self.a.data = [self.a.data.clone(), b_swap].concat();
-        self.b.data = [self.b.data.clone(), a_swap].concat();
+    fn crossover<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        match self.kind {
+            CrossoverKind::SinglePoint => {
+                let length = self.a.data.len();
+                let cut_point = self.cut.cut_point(rng, length);
+                let a_swap = self.a.data.split_off(cut_point);
+                let b_swap = self.b.data.split_off(cut_point);
+                self.a.data.extend(b_swap);
+                self.b.data.extend(a_swap);
+            }
+            CrossoverKind::TwoPoint => {
+                let mut cuts = rand::seq::index::sample(rng, self.a.data.len() + 1, 2).into_vec();
+                cuts.sort_unstable();
+                self.a.data[cuts[0]..cuts[1]].swap_with_slice(&mut self.b.data[cuts[0]..cuts[1]]);
+            }
+            CrossoverKind::Uniform => {
+                let mask = uniform_mask(rng, self.a.data.len());
+                swap_unmasked_genes(self.a, self.b, &mask);
+            }
+        }
+
+        self.a.repair_categories();
+        self.b.repair_categories();
     }
 }
 
 impl genetic::Mutate for Pair<'_> {
-    fn mutate(&mut self, n: usize, prob: f64) {
-        self.a.mutate(n, prob);
-        self.b.mutate(n, prob);
+    fn mutate<R: Rng + ?Sized>(&mut self, rng: &mut R, n: usize, prob: f64) {
+        match self.mutation {
+            MutationMode::ExactCount => {
+                self.a.mutate(rng, n, prob);
+                self.b.mutate(rng, n, prob);
+            }
+            MutationMode::PerGene => {
+                self.a.mutate_per_gene(rng, prob);
+                self.b.mutate_per_gene(rng, prob);
+            }
+        }
     }
 }
 
+// mask[i] is true when child a inherits gene i from parent a (and child b from parent b)
+fn uniform_mask<R: Rng + ?Sized>(rng: &mut R, length: usize) -> Vec<bool> {
+    (0..length).map(|_| rng.random_bool(0.5)).collect()
+}
+
+fn swap_unmasked_genes(a: &mut Genome, b: &mut Genome, mask: &[bool]) {
+    mask.iter().enumerate().for_each(|(i, keep)| {
+        if !keep {
+            std::mem::swap(&mut a.data[i], &mut b.data[i]);
+        }
+    });
+}
+
+#[allow(dead_code)]
+fn uniform_crossover_with_mask<R: Rng + ?Sized>(
+    rng: &mut R,
+    a: &Genome,
+    b: &Genome,
+) -> (Genome, Genome, Vec<bool>) {
+    let mask = uniform_mask(rng, a.data.len());
+    let mut child_a = a.clone();
+    let mut child_b = b.clone();
+    swap_unmasked_genes(&mut child_a, &mut child_b, &mask);
+    child_a.repair_categories();
+    child_b.repair_categories();
+
+    (child_a, child_b, mask)
+}
+
 impl Eq for Genome {}
 
+impl genetic::Individual for Genome {
+    const SENSE: Sense = Sense::Maximize;
+}
+
+impl genetic::Genome for Genome {
+    type Gene = u32;
+
+    fn genes(&self) -> &[u32] {
+        &self.data
+    }
+
+    fn random<R: Rng + ?Sized>(&self, rng: &mut R) -> Self {
+        let densities = Initialization::Uniform.densities(&self.things);
+        Genome::new_with_rng(
+            &self.things,
+            self.limit,
+            self.volume_limit,
+            self.mode,
+            &self.forced_items,
+            &densities,
+            rng,
+        )
+    }
+
+    fn validate(&self, operator: &str) {
+        Genome::validate(self, operator);
+    }
+
+    fn verify(&self) -> bool {
+        self.fitness() == self.compute_fitness()
+    }
+}
+
 impl Ord for Genome {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.fitness().cmp(&self.fitness())
+        Genome::SENSE.compare(self.fitness(), other.fitness())
     }
 }
 
@@ -146,63 +939,1081 @@ impl PartialOrd for Genome {
     }
 }
 
-fn run_evolution(
-    population: &mut Population,
-    target: u32,
-    generation_limit: usize,
-) -> Option<(&Genome, usize)> {
-    for i in 0..generation_limit {
-        population.data.sort();
-        if population.data.first().unwrap().fitness() >= target {
-            return Some((population.data.first().unwrap(), i));
+// bits set in one packed genome but not the other
+fn packed_hamming(a: &[u64], b: &[u64]) -> usize {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a ^ b).count_ones() as usize)
+        .sum()
+}
+
+// LP relaxation upper bound: fill greedily by value density and take a
+// fraction of the first item whose copies no longer fit. volume is ignored, which
+// keeps it an upper bound
+fn fractional_knapsack_bound(things: &[Thing], limit: u32) -> f64 {
+    let density = |thing: &Thing| thing.value as f64 / thing.weight as f64;
+    let mut things: Vec<&Thing> = things.iter().collect();
+    things.sort_by(|a, b| density(b).total_cmp(&density(a)));
+
+    let mut capacity = limit as f64;
+    let mut bound = 0.0;
+    for thing in things {
+        if capacity <= 0.0 {
+            break;
+        }
+
+        let taken = (capacity / thing.weight as f64).min(thing.max_count as f64);
+        bound += taken * thing.value as f64;
+        capacity -= taken * thing.weight as f64;
+    }
+
+    bound
+}
+
+// the items and limits run() packs
+fn demo_items() -> Vec<Thing> {
+    vec![
+        Thing::new("Laptop", 500, 2200, 300),
+        Thing::new("Headphones", 150, 160, 150),
+        Thing::new("Coffee Mug", 60, 350, 120),
+        Thing::new("Notepad", 40, 333, 60),
+        Thing::new("Water Bottle", 30, 192, 75),
+        Thing::new("Mints", 5, 25, 5),
+        Thing::new("Socks", 10, 38, 20),
+        Thing::new("Tissues", 15, 80, 40),
+        Thing::new("Phone", 500, 200, 15),
+        Thing::new("Baseball Cap", 100, 70, 90),
+    ]
+}
+
+// "-" reads from stdin so items can be piped in
+fn read_items(path: &str) -> csv::Result<Vec<Thing>> {
+    if path == "-" {
+        return parse_items(std::io::stdin().lock());
+    }
+    parse_items(std::fs::File::open(path)?)
+}
+
+// a csv with name,value,weight,volume columns and optional max_count and
+// category ones
+fn parse_items<R: std::io::Read>(input: R) -> csv::Result<Vec<Thing>> {
+    csv::Reader::from_reader(input).deserialize().collect()
+}
+
+// what run() and solve() do, parsed from the knapsack subcommand
+#[derive(Debug, Clone, clap::Parser)]
+pub struct Options {
+    // a csv of name,value,weight,volume items, - for stdin. the demo items
+    // when left out
+    pub items: Option<String>,
+    #[arg(long, default_value_t = 3000)]
+    pub limit: u32,
+    #[arg(long, default_value_t = 700)]
+    pub volume_limit: u32,
+    #[arg(long, default_value_t = 10)]
+    pub pop_size: u32,
+    #[arg(long, default_value_t = 1000)]
+    pub generations: usize,
+    #[arg(long, default_value_t = 1310.0)]
+    pub target: f64,
+    // skip, penalty:RATE to charge for going over the limits, or
+    // scalarized:VALUE_WEIGHT:WEIGHT_PENALTY to trade value against weight
+    #[arg(long, default_value = "skip")]
+    pub fitness: FitnessMode,
+    // indices of items every packing must hold, e.g. 0,8
+    #[arg(long, value_delimiter = ',')]
+    pub forced_items: Vec<usize>,
+    // exact to step mutation_count genes, per-gene to step each with the
+    // mutation probability
+    #[arg(long, default_value = "exact")]
+    pub mutation_mode: MutationMode,
+    // uniform, or ratio to set items with a better value/weight ratio more often
+    #[arg(long, default_value = "uniform")]
+    pub init: Initialization,
+    #[command(flatten)]
+    pub run: RunFlags,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        clap::Parser::parse_from(["knapsack"])
+    }
+}
+
+// the population and config run() uses
+fn setup<R: Rng + ?Sized>(options: &Options, rng: &mut R) -> (Population, EvolutionConfig) {
+    let things = match &options.items {
+        Some(path) => read_items(path).unwrap_or_else(|err| panic!("{path}: {err}")),
+        None => demo_items(),
+    };
+    let mut population = Population::new(
+        options.pop_size,
+        &things,
+        options.limit,
+        options.volume_limit,
+        options.fitness,
+        &options.forced_items,
+        options.init,
+        rng,
+    );
+    population.switch_operator_on_stall = true;
+    population.inject_mutant_every = Some(10);
+    population.mutation_mode = options.mutation_mode;
+    let mut config = EvolutionConfig {
+        record_best_genomes: true,
+        operator_stats: true,
+        ..Default::default()
+    };
+    options.run.apply(&mut config);
+    (population, config)
+}
+
+// run() without any output, returning the value of the best packing found
+pub fn solve(seed: u64, options: &Options) -> f64 {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (mut population, config) = setup(options, &mut rng);
+    run_evolution(
+        &mut population,
+        &config,
+        &GenerationLimit(options.generations),
+        &mut |_: usize, _: &Population, _: bool| {},
+        &mut rng,
+    )
+    .fitness
+}
+
+pub fn run(seed: Option<u64>, options: &Options) {
+    let seed = seed.unwrap_or_else(|| rng().random());
+    println!("knapsack seed: {seed}");
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let (mut population, config) = setup(options, &mut rng);
+    let termination = Or(
+        TargetFitness {
+            target: options.target,
+            sense: Genome::SENSE,
+        },
+        GenerationLimit(options.generations),
+    );
+    let solution = run_evolution(
+        &mut population,
+        &config,
+        &termination,
+        &mut |i, population: &Population, improved| {
+            if improved {
+                println!(
+                    "generation: {} | best solution so far: {} | diversity: {:.3}",
+                    i,
+                    population.state.convergence.best,
+                    population.diversity(),
+                );
+            }
+        },
+        &mut rng,
+    );
+
+    println!(
+        "{} -- {:?} -- {:?} -- met target: {}",
+        solution.generations, solution.fitness, solution.best.data, solution.met_target
+    );
+    debug_assert!(
+        genetic::Genome::verify(&solution.best),
+        "stale fitness cache on the best genome"
+    );
+    print!("{}", solution.best.report());
+    let improvements: Vec<f64> = solution
+        .best_genomes
+        .iter()
+        .map(|genome| genome.fitness())
+        .collect();
+    println!("improvements: {:?}", improvements);
+
+    let bound = fractional_knapsack_bound(&solution.best.things, options.limit);
+    println!(
+        "bound: {} -- {:.1}% of bound",
+        bound,
+        100.0 * solution.fitness / bound
+    );
+    println!("evaluations: {}", solution.metrics.evaluations);
+    println!("feasible fraction: {}", population.feasible_fraction());
+    println!("entropy: {:.3}", population.entropy());
+    print!("{}", solution.metrics.operator_stats);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genetic::{EvolutionMode, ReplacementStrategy, SelectionStrategy, Similarity};
+
+    fn genome(things: &[Thing], data: Vec<u32>, forced_items: &[usize]) -> Genome {
+        Genome {
+            data,
+            things: things.into(),
+            limit: 3000,
+            volume_limit: 700,
+            mode: FitnessMode::Constrained(ConstraintStrategy::Skip),
+            forced_items: forced_items.to_vec(),
+            cached_fitness: OnceLock::new(),
+        }
+    }
+
+    #[test]
+    fn repair_categories_keeps_forced_item_over_earlier_one() {
+        let things = vec![
+            Thing::with_category("Tent", 100, 500, 100, 0),
+            Thing::with_category("Hammock", 80, 300, 50, 0),
+            Thing::new("Rope", 10, 50, 10),
+        ];
+        let mut genome = genome(&things, vec![1, 1, 1], &[1]);
+        genome.repair_categories();
+        assert_eq!(genome.data, vec![0, 1, 1]);
+        genome.validate("repair");
+    }
+
+    fn demo_things() -> Vec<Thing> {
+        vec![
+            Thing::new("Laptop", 500, 2200, 300),
+            Thing::new("Headphones", 150, 160, 150),
+            Thing::new("Coffee Mug", 60, 350, 120),
+            Thing::new("Notepad", 40, 333, 60),
+            Thing::new("Phone", 500, 200, 15),
+            Thing::new("Baseball Cap", 100, 70, 90),
+        ]
+    }
+
+    fn population(pop_size: u32, rng: &mut StdRng) -> Population {
+        Population::new(
+            pop_size,
+            &demo_things(),
+            3000,
+            700,
+            FitnessMode::Constrained(ConstraintStrategy::Skip),
+            &[],
+            Initialization::Uniform,
+            rng,
+        )
+    }
+
+    #[test]
+    #[should_panic(expected = "knapsack limit must be greater than zero")]
+    fn zero_limit_is_rejected() {
+        Population::new(
+            10,
+            &demo_things(),
+            0,
+            700,
+            FitnessMode::Constrained(ConstraintStrategy::Skip),
+            &[],
+            Initialization::Uniform,
+            &mut StdRng::seed_from_u64(1),
+        );
+    }
+
+    #[test]
+    fn comma_replacement_survives_a_spent_budget() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut population = population(10, &mut rng);
+        let config = EvolutionConfig {
+            replacement: ReplacementStrategy::Comma,
+            max_evaluations: Some(10),
+            ..Default::default()
+        };
+        let result = run_evolution(
+            &mut population,
+            &config,
+            &GenerationLimit(20),
+            &mut |_: usize, _: &Population, _: bool| {},
+            &mut rng,
+        );
+        assert_eq!(result.generations, 0);
+        assert_eq!(population.data.len(), 10);
+        assert_eq!(result.history.len(), 1);
+    }
+
+    #[test]
+    fn history_ends_with_the_returned_fitness() {
+        for (target, generations) in [(1310.0, 200), (f64::MAX, 15)] {
+            let mut rng = StdRng::seed_from_u64(6);
+            let mut population = population(10, &mut rng);
+            let termination = Or(
+                TargetFitness {
+                    target,
+                    sense: Genome::SENSE,
+                },
+                GenerationLimit(generations),
+            );
+            let result = run_evolution(
+                &mut population,
+                &EvolutionConfig::default(),
+                &termination,
+                &mut |_: usize, _: &Population, _: bool| {},
+                &mut rng,
+            );
+            assert_eq!(result.history.len(), result.generations + 1);
+            assert_eq!(*result.history.last().unwrap(), result.fitness);
         }
+    }
+
+    #[test]
+    fn evaluate_parallel_returns_fitness_in_genome_order() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let population = population(20, &mut rng);
+        let fitnesses = genetic::evaluate_parallel(&population.data);
+        let expected: Vec<f64> = population
+            .data
+            .iter()
+            .map(|genome| genome.compute_fitness())
+            .collect();
+        assert_eq!(fitnesses, expected);
+    }
 
-        let mut new_population = population.clone();
-        new_population.data = new_population.data.get(0..=1).unwrap().to_vec();
+    #[test]
+    fn verify_catches_a_stale_cache() {
+        let mut genome = genome(&demo_things(), vec![1, 0, 0, 0, 1, 0], &[]);
+        assert!(genetic::Genome::verify(&genome));
 
-        for _ in (0..population.data.len()).step_by(2) {
-            let parents = population.selection(2);
-            let mut a = parents.data.first().as_mut().unwrap().clone();
-            let mut b = parents.data.last().as_mut().unwrap().clone();
+        genome.data[5] = 1;
+        assert!(!genetic::Genome::verify(&genome));
+        genome.invalidate_fitness();
+        assert!(genetic::Genome::verify(&genome));
+    }
+
+    // more than one word of genes so the packing crosses a u64 boundary
+    fn random_bits(rng: &mut StdRng) -> (Vec<Thing>, Genome) {
+        let things: Vec<Thing> = (0..150)
+            .map(|i| Thing::new(&i.to_string(), i, i, i))
+            .collect();
+        let data = (0..things.len()).map(|_| rng.random_range(0..=1)).collect();
+        let genome = genome(&things, data, &[]);
+        (things, genome)
+    }
+
+    #[test]
+    fn bitvec_round_trips() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let (things, genome) = random_bits(&mut rng);
+        let bits = genome.to_bitvec();
+        assert_eq!(bits.len(), 3);
+        let restored = Genome::from_bitvec(&bits, &things.into(), 3000, 700, genome.mode, &[]);
+        assert_eq!(restored.data, genome.data);
+    }
+
+    #[test]
+    fn popcount_hamming_matches_naive_count() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..20 {
+            let (_, a) = random_bits(&mut rng);
+            let (_, b) = random_bits(&mut rng);
+            assert_eq!(
+                packed_hamming(&a.to_bitvec(), &b.to_bitvec()),
+                a.hamming(&b)
+            );
+        }
+    }
+
+    #[test]
+    fn report_totals_match_skip_fitness() {
+        let things = vec![
+            Thing::new("Laptop", 500, 2200, 300),
+            // too heavy after the laptop, the cheap items after it still fit
+            Thing::new("Anvil", 90, 900, 10),
+            Thing::new("Phone", 500, 200, 15),
+            Thing::new("Notepad", 40, 333, 60),
+        ];
+        let genome = genome(&things, vec![1, 1, 1, 1], &[]);
+        let report = genome.report();
+        assert_eq!(genome.fitness(), 1040.0);
+        assert!(report.contains("total value: 1040  total weight: 2733"));
+        assert!(report.contains("skipped: 1"));
+    }
+
+    #[test]
+    fn item_over_the_volume_limit_is_skipped() {
+        let things = vec![
+            Thing::new("Laptop", 500, 2200, 300),
+            // light enough, but 800 volume with the laptop against a limit of 700
+            Thing::new("Tent", 400, 100, 500),
+            Thing::new("Notepad", 40, 333, 60),
+        ];
+        let genome = genome(&things, vec![1, 1, 1], &[]);
+        assert_eq!(genome.fitness(), 540.0);
+    }
+
+    #[test]
+    fn uniform_crossover_mask_rebuilds_the_children() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let (_, a) = random_bits(&mut rng);
+        let (_, b) = random_bits(&mut rng);
+        let (child_a, child_b, mask) = uniform_crossover_with_mask(&mut rng, &a, &b);
+        for (i, keep) in mask.iter().enumerate() {
+            let (from_a, from_b) = if *keep { (&a, &b) } else { (&b, &a) };
+            assert_eq!(child_a.data[i], from_a.data[i]);
+            assert_eq!(child_b.data[i], from_b.data[i]);
+        }
+    }
+
+    #[test]
+    fn nsga2_front_is_mutually_non_dominated() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let mut population = population(20, &mut rng);
+        let front = population.nsga2(&EvolutionConfig::default(), 20, &mut rng);
+        assert!(!front.is_empty());
+        for a in &front {
+            for b in &front {
+                assert!(!genetic::dominates(
+                    &OBJECTIVE_SENSES,
+                    &a.objectives(),
+                    &b.objectives()
+                ));
+            }
+        }
+    }
+
+    fn cross(a: &mut Genome, b: &mut Genome, kind: CrossoverKind, seed: u64) {
+        let mut pair = Pair {
+            a,
+            b,
+            cut: CutStrategy::Random,
+            kind,
+            mutation: MutationMode::PerGene,
+        };
+        pair.crossover(&mut StdRng::seed_from_u64(seed));
+    }
+
+    #[test]
+    fn fixed_and_mid_point_cuts_give_exact_children() {
+        let things = demo_things();
+        let cases = [
+            (CutStrategy::Fixed(2), [0, 0, 1, 1, 1, 1]),
+            (CutStrategy::MidPoint, [0, 0, 0, 1, 1, 1]),
+        ];
+        for (cut, expected) in cases {
+            let mut a = genome(&things, vec![0; 6], &[]);
+            let mut b = genome(&things, vec![1; 6], &[]);
             let mut pair = Pair {
                 a: &mut a,
                 b: &mut b,
+                cut,
+                kind: CrossoverKind::SinglePoint,
+                mutation: MutationMode::PerGene,
             };
-            pair.crossover();
-            pair.mutate(1, 0.5);
-            new_population.data.push(pair.a.to_owned());
-            new_population.data.push(pair.b.to_owned());
+            pair.crossover(&mut StdRng::seed_from_u64(0));
+            assert_eq!(a.data, expected);
+            assert_eq!(b.data, expected.map(|gene| 1 - gene));
         }
+    }
 
-        *population = new_population;
+    #[test]
+    fn two_point_crossover_swaps_one_window() {
+        let things = demo_things();
+        for seed in 0..20 {
+            let mut a = genome(&things, vec![0; 6], &[]);
+            let mut b = genome(&things, vec![1; 6], &[]);
+            cross(&mut a, &mut b, CrossoverKind::TwoPoint, seed);
+
+            // outside the window a keeps its 0s, so its 1s are contiguous
+            let ones: Vec<usize> = (0..6).filter(|i| a.data[*i] == 1).collect();
+            assert!(ones.windows(2).all(|w| w[1] == w[0] + 1), "{:?}", a.data);
+            assert!((0..6).all(|i| a.data[i] + b.data[i] == 1));
+        }
     }
 
-    None
-}
+    #[test]
+    fn uniform_crossover_takes_every_gene_from_a_parent() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for seed in 0..20 {
+            let (_, a) = random_bits(&mut rng);
+            let (_, b) = random_bits(&mut rng);
+            let (mut x, mut y) = (a.clone(), b.clone());
+            cross(&mut x, &mut y, CrossoverKind::Uniform, seed);
+            for i in 0..a.data.len() {
+                assert!(x.data[i] == a.data[i] || x.data[i] == b.data[i]);
+                assert_eq!(x.data[i] + y.data[i], a.data[i] + b.data[i]);
+            }
+        }
+    }
 
-pub fn run() {
-    let limit = 3000;
-    let things = vec![
-        Thing::new("Laptop", 500, 2200),
-        Thing::new("Headphones", 150, 160),
-        Thing::new("Coffee Mug", 60, 350),
-        Thing::new("Notepad", 40, 333),
-        Thing::new("Water Bottle", 30, 192),
-        Thing::new("Mints", 5, 25),
-        Thing::new("Socks", 10, 38),
-        Thing::new("Tissues", 15, 80),
-        Thing::new("Phone", 500, 200),
-        Thing::new("Baseball Cap", 100, 70),
-    ];
+    #[test]
+    fn run_many_orders_worst_mean_best() {
+        let stats = genetic::run_many(
+            |rng| population(10, rng),
+            &EvolutionConfig::default(),
+            &GenerationLimit(20),
+            5,
+            1,
+        );
+        assert_eq!(stats.runs, 5);
+        // maximising, so the worst run has the lowest value
+        assert!(stats.worst <= stats.mean && stats.mean <= stats.best);
+        assert!(stats.std_dev >= 0.0);
+    }
 
-    let mut population = Population::new(10, &things, limit);
-    let solution = run_evolution(&mut population, 1310, 1000).expect("no solution found");
+    #[test]
+    fn max_count_items_pack_more_than_once() {
+        let things = vec![
+            Thing::with_max_count("Sock", 5, 10, 5, 4),
+            Thing::new("Boot", 50, 800, 100),
+        ];
+        let genome = genome(&things, vec![3, 1], &[]);
+        genome.validate("test");
+        assert_eq!(genome.fitness(), 65.0);
+    }
 
-    println!(
-        "{} -- {:?} -- {:?}",
-        solution.1,
-        solution.0.fitness(),
-        solution.0.data
-    );
-    println!("{:?}", solution.0.things);
+    #[test]
+    fn operator_tally_matches_the_matings() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut population = population(10, &mut rng);
+        // one mating per generation
+        let config = EvolutionConfig {
+            mode: EvolutionMode::SteadyState { replacements: 2 },
+            crossover_rate: 0.5,
+            operator_stats: true,
+            ..Default::default()
+        };
+        let result = run_evolution(
+            &mut population,
+            &config,
+            &GenerationLimit(20),
+            &mut |_: usize, _: &Population, _: bool| {},
+            &mut rng,
+        );
+
+        let counts = &result.metrics.operator_stats.counts;
+        assert_eq!(counts["bit flip mutation"].applied, 20);
+        let crossovers: usize = counts
+            .iter()
+            .filter(|(operator, _)| operator.ends_with("crossover"))
+            .map(|(_, count)| count.applied)
+            .sum();
+        assert!(crossovers > 0 && crossovers < 20);
+        // the crossed children are evaluated once more before mutation
+        assert_eq!(result.metrics.evaluations, 10 + 2 * 20 + 2 * crossovers);
+    }
+
+    #[test]
+    fn bigger_weight_penalty_packs_lighter() {
+        let weights: Vec<u32> = [0.1, 1.0]
+            .iter()
+            .map(|weight_penalty| {
+                let mut rng = StdRng::seed_from_u64(5);
+                let mode = FitnessMode::Scalarized {
+                    value_weight: 1.0,
+                    weight_penalty: *weight_penalty,
+                };
+                let mut population = Population::new(
+                    20,
+                    &demo_things(),
+                    3000,
+                    700,
+                    mode,
+                    &[],
+                    Initialization::Uniform,
+                    &mut rng,
+                );
+                let result = run_evolution(
+                    &mut population,
+                    &EvolutionConfig::default(),
+                    &GenerationLimit(100),
+                    &mut |_: usize, _: &Population, _: bool| {},
+                    &mut rng,
+                );
+                result.best.totals().1
+            })
+            .collect();
+        assert!(weights[1] < weights[0], "{weights:?}");
+    }
+
+    #[test]
+    fn penalty_scores_an_infeasible_genome_below_skip() {
+        // every item once is 313 over the weight limit and 35 over the volume one
+        let mut overfull = genome(&demo_things(), vec![1; 6], &[]);
+        assert_eq!(overfull.fitness(), 1310.0);
+
+        overfull.mode = "penalty:1".parse().unwrap();
+        overfull.invalidate_fitness();
+        assert_eq!(overfull.fitness(), 1350.0 - 348.0);
+
+        // a feasible genome scores the same either way
+        let mut feasible = genome(&demo_things(), vec![1, 1, 1, 0, 1, 1], &[]);
+        let skipped = feasible.fitness();
+        feasible.mode = "penalty:1".parse().unwrap();
+        feasible.invalidate_fitness();
+        assert_eq!(feasible.fitness(), skipped);
+    }
+
+    #[test]
+    fn exact_count_mutation_changes_exactly_n_genes() {
+        let things: Vec<Thing> = (0..12)
+            .map(|i| Thing::with_category(&i.to_string(), 10, 10, 10, i % 3))
+            .chain((0..6).map(|i| Thing::new("free", i, i, i)))
+            .collect();
+        let mut rng = StdRng::seed_from_u64(3);
+        for n in 1..=4 {
+            for _ in 0..50 {
+                let data = (0..things.len()).map(|_| rng.random_range(0..=1)).collect();
+                let mut before = genome(&things, data, &[]);
+                before.repair_categories();
+                let mut after = before.clone();
+                genetic::Mutate::mutate(&mut after, &mut rng, n, 0.0);
+                assert_eq!(after.hamming(&before), n, "{:?}", before.data);
+            }
+        }
+    }
+
+    #[test]
+    fn per_gene_mutation_changes_about_p_times_len_genes() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let (_, before) = random_bits(&mut rng);
+        let trials = 200;
+        let changed: usize = (0..trials)
+            .map(|_| {
+                let mut after = before.clone();
+                after.mutate_per_gene(&mut rng, 0.1);
+                after.hamming(&before)
+            })
+            .sum();
+        let mean = changed as f64 / trials as f64;
+        assert!((mean - 15.0).abs() < 1.0, "{mean}");
+    }
+
+    #[test]
+    fn ratio_biased_init_sets_high_ratio_items_more_often() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let population = Population::new(
+            500,
+            &demo_things(),
+            3000,
+            700,
+            FitnessMode::Constrained(ConstraintStrategy::Skip),
+            &[],
+            Initialization::RatioBiased,
+            &mut rng,
+        );
+        let set = |i: usize| population.data.iter().filter(|g| g.data[i] > 0).count();
+        // Phone is worth 2.5 per unit of weight, Coffee Mug about 0.17
+        assert!(set(4) > 2 * set(2), "{} {}", set(4), set(2));
+    }
+
+    #[test]
+    fn parse_items_reads_from_a_cursor() {
+        let csv = "name,value,weight,volume,max_count\nTent,100,500,100,1\nRope,10,50,10,3\n";
+        let things = parse_items(std::io::Cursor::new(csv)).unwrap();
+        let names: Vec<&str> = things.iter().map(|thing| thing.name.as_str()).collect();
+        assert_eq!(names, ["Tent", "Rope"]);
+        assert_eq!((things[1].value, things[1].max_count), (10, 3));
+
+        // max_count and category are optional
+        let things = parse_items(std::io::Cursor::new(
+            "name,value,weight,volume\nMap,5,5,5\n",
+        ))
+        .unwrap();
+        assert_eq!((things[0].max_count, things[0].category), (1, None));
+    }
+
+    #[test]
+    fn cloned_population_shares_its_context() {
+        let population = population(5, &mut StdRng::seed_from_u64(1));
+        let mut cloned = population.clone();
+        cloned.data[0].data = population.data[0]
+            .data
+            .iter()
+            .map(|gene| 1 - gene)
+            .collect();
+
+        assert_ne!(cloned.data[0].data, population.data[0].data);
+        for (a, b) in cloned.data.iter().zip(&population.data) {
+            assert!(Arc::ptr_eq(&a.things, &b.things));
+            assert!(Arc::ptr_eq(&a.things, &population.data[0].things));
+        }
+    }
+
+    #[test]
+    fn rank_selection_follows_the_ranks() {
+        // one item each, worth 500, 150, 100, 60 and 40
+        let mut genomes: Vec<Genome> = [0, 1, 5, 2, 3]
+            .iter()
+            .map(|item| {
+                let mut data = vec![0; 6];
+                data[*item] = 1;
+                genome(&demo_things(), data, &[])
+            })
+            .collect();
+        genomes.sort();
+
+        let mut rng = StdRng::seed_from_u64(5);
+        let trials = 15000;
+        let mut picks = [0; 5];
+        for _ in 0..trials {
+            let picked = genetic::select(&genomes, SelectionStrategy::Rank, 0.0, &mut rng, 1);
+            picks[genomes
+                .iter()
+                .position(|g| g.data == picked[0].data)
+                .unwrap()] += 1;
+        }
+        // weights 5, 4, 3, 2, 1 out of 15
+        for (rank, count) in picks.iter().enumerate() {
+            let expected = (5 - rank) as f64 / 15.0 * trials as f64;
+            assert!(
+                (*count as f64 - expected).abs() < 0.1 * expected,
+                "{picks:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn sort_puts_the_most_valuable_packing_first() {
+        let mut population = population(20, &mut StdRng::seed_from_u64(2));
+        population.data.sort();
+        let values: Vec<f64> = population.data.iter().map(|g| g.fitness()).collect();
+        assert!(values.windows(2).all(|w| w[0] >= w[1]), "{values:?}");
+        assert_eq!(genetic::worst(&population.data).fitness(), values[19]);
+    }
+
+    // runs the shared loop for generations without an observer
+    fn evolve(
+        population: &mut Population,
+        config: &EvolutionConfig,
+        generations: usize,
+        rng: &mut StdRng,
+    ) -> genetic::RunResult<Genome, f64> {
+        run_evolution(
+            population,
+            config,
+            &GenerationLimit(generations),
+            &mut |_: usize, _: &Population, _: bool| {},
+            rng,
+        )
+    }
+
+    #[test]
+    fn elite_count_keeps_the_top_four() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut population = population(20, &mut rng);
+        population.data.sort();
+        let elites = genetic::unique_elites(&population.data, 4);
+        let config = EvolutionConfig {
+            elite_count: 4,
+            ..Default::default()
+        };
+        evolve(&mut population, &config, 1, &mut rng);
+        for elite in &elites {
+            assert!(
+                population
+                    .data
+                    .iter()
+                    .any(|genome| genome.data == elite.data),
+                "lost {:?}",
+                elite.data
+            );
+        }
+    }
+
+    #[test]
+    fn steady_state_replaces_only_the_worst() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let mut population = population(20, &mut rng);
+        population.data.sort();
+        let before: Vec<Vec<u32>> = population.data.iter().map(|g| g.data.clone()).collect();
+        let config = EvolutionConfig {
+            mode: EvolutionMode::SteadyState { replacements: 2 },
+            ..Default::default()
+        };
+        evolve(&mut population, &config, 1, &mut rng);
+
+        assert_eq!(population.data.len(), 20);
+        // every genome but the worst two is still there
+        let mut after: Vec<Vec<u32>> = population.data.iter().map(|g| g.data.clone()).collect();
+        for kept in &before[..18] {
+            let i = after.iter().position(|data| data == kept).unwrap();
+            after.swap_remove(i);
+        }
+    }
+
+    #[test]
+    fn plus_never_loses_the_best_and_comma_can() {
+        let history = |replacement| {
+            let mut rng = StdRng::seed_from_u64(13);
+            let mut population = population(10, &mut rng);
+            let config = EvolutionConfig {
+                replacement,
+                ..Default::default()
+            };
+            evolve(&mut population, &config, 50, &mut rng).history
+        };
+        let plus = history(ReplacementStrategy::Plus);
+        assert!(plus.windows(2).all(|w| w[1] >= w[0]), "{plus:?}");
+        let comma = history(ReplacementStrategy::Comma);
+        assert!(comma.windows(2).any(|w| w[1] < w[0]), "{comma:?}");
+    }
+
+    #[test]
+    fn tied_survivors_stay_put_without_drift() {
+        let survivors = |accept_equal_offspring| {
+            let mut rng = StdRng::seed_from_u64(14);
+            // every packing scores 0, so all genomes tie
+            let mode = FitnessMode::Scalarized {
+                value_weight: 0.0,
+                weight_penalty: 0.0,
+            };
+            let things = demo_things();
+            let mut population = Population::new(
+                10,
+                &things,
+                3000,
+                700,
+                mode,
+                &[],
+                Initialization::Uniform,
+                &mut rng,
+            );
+            let config = EvolutionConfig {
+                elite_count: 4,
+                accept_equal_offspring,
+                ..Default::default()
+            };
+            let elites: Vec<Vec<u32>> = genetic::unique_elites(&population.data, 4)
+                .into_iter()
+                .map(|genome| genome.data)
+                .collect();
+            evolve(&mut population, &config, 1, &mut rng);
+            let front: Vec<Vec<u32>> = population.data[..elites.len()]
+                .iter()
+                .map(|genome| genome.data.clone())
+                .collect();
+            (elites, front)
+        };
+
+        let (elites, front) = survivors(false);
+        assert_eq!(front, elites);
+        // with drift on the tied offspring take their place at the front
+        let (elites, front) = survivors(true);
+        assert_ne!(front, elites);
+    }
+
+    #[test]
+    fn unreachable_target_runs_to_the_generation_limit() {
+        let mut rng = StdRng::seed_from_u64(15);
+        let mut population = population(10, &mut rng);
+        // every item together is worth 1350, short of the target
+        let termination = Or(
+            TargetFitness {
+                target: 5000.0,
+                sense: Genome::SENSE,
+            },
+            GenerationLimit(30),
+        );
+        let result = run_evolution(
+            &mut population,
+            &EvolutionConfig::default(),
+            &termination,
+            &mut |_: usize, _: &Population, _: bool| {},
+            &mut rng,
+        );
+        assert!(!result.met_target);
+        assert_eq!(result.generations, 30);
+        assert_eq!(result.fitness, result.best.fitness());
+    }
+
+    #[test]
+    fn observer_is_called_once_per_generation() {
+        let mut rng = StdRng::seed_from_u64(17);
+        let mut population = population(10, &mut rng);
+        let mut generations = vec![];
+        let result = run_evolution(
+            &mut population,
+            &EvolutionConfig::default(),
+            &GenerationLimit(25),
+            &mut |generation: usize, _: &Population, _: bool| generations.push(generation),
+            &mut rng,
+        );
+        // generations 0..=25, the last one seen before stopping
+        assert_eq!(generations, (0..=result.generations).collect::<Vec<_>>());
+        assert_eq!(generations.len(), 26);
+    }
+
+    #[test]
+    fn converged_population_resets_to_reset_retain() {
+        let mut rng = StdRng::seed_from_u64(18);
+        let mut population = population(10, &mut rng);
+        let config = EvolutionConfig {
+            reset_retain: Some(3),
+            ..Default::default()
+        };
+        population.state.convergence.threshold = config.stagnation_limit;
+        population.state.convergence.stagnant = config.stagnation_limit + 1;
+        let evaluations = population.state.evaluations;
+
+        population.after_generation(1, &config, &mut rng);
+        assert_eq!(population.state.evaluations, evaluations + 7);
+        assert_eq!(population.state.convergence.stagnant, 0);
+    }
+
+    #[test]
+    fn same_seed_evolves_the_same_genomes() {
+        let run = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut population = population(10, &mut rng);
+            let result = evolve(&mut population, &EvolutionConfig::default(), 30, &mut rng);
+            let genomes: Vec<Vec<u32>> = population.data.iter().map(|g| g.data.clone()).collect();
+            (result.best.data, genomes)
+        };
+        assert_eq!(run(19), run(19));
+    }
+
+    #[test]
+    fn forced_items_stay_selected_through_a_run() {
+        let mut rng = StdRng::seed_from_u64(20);
+        let mode = FitnessMode::Constrained(ConstraintStrategy::Skip);
+        let things = demo_things();
+        let forced = [2, 3];
+        let mut population = Population::new(
+            10,
+            &things,
+            3000,
+            700,
+            mode,
+            &forced,
+            Initialization::Uniform,
+            &mut rng,
+        );
+        population.inject_mutant_every = Some(5);
+        let config = EvolutionConfig {
+            mutation_n: 3,
+            ..Default::default()
+        };
+        let result = run_evolution(
+            &mut population,
+            &config,
+            &GenerationLimit(50),
+            &mut |_: usize, population: &Population, _: bool| {
+                for genome in &population.data {
+                    assert!(
+                        forced.iter().all(|i| genome.data[*i] > 0),
+                        "{:?}",
+                        genome.data
+                    );
+                }
+            },
+            &mut rng,
+        );
+        assert!(forced.iter().all(|i| result.best.data[*i] > 0));
+    }
+
+    #[test]
+    fn diversity_is_the_mean_pairwise_hamming_fraction() {
+        let things = demo_things();
+        let mut population = population(3, &mut StdRng::seed_from_u64(1));
+        population.data = vec![genome(&things, vec![1, 1, 0, 0, 0, 0], &[]); 3];
+        assert_eq!(population.diversity(), 0.0);
+
+        // 2, 4 and 2 of 6 genes differ between the pairs
+        population.data = vec![
+            genome(&things, vec![0, 0, 0, 0, 0, 0], &[]),
+            genome(&things, vec![1, 1, 0, 0, 0, 0], &[]),
+            genome(&things, vec![1, 1, 1, 1, 0, 0], &[]),
+        ];
+        assert!((population.diversity() - 4.0 / 9.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn random_population_has_more_entropy_than_a_converged_one() {
+        let mut population = population(20, &mut StdRng::seed_from_u64(2));
+        let random = population.entropy();
+        population.data = vec![population.data[0].clone(); 20];
+        assert_eq!(population.entropy(), 0.0);
+        assert!(random > 0.5, "{random}");
+    }
+
+    #[test]
+    fn genome_is_most_similar_to_itself_and_least_to_its_complement() {
+        let things = demo_things();
+        let a = genome(&things, vec![1, 0, 1, 1, 0, 0], &[]);
+        let complement = genome(&things, vec![0, 1, 0, 0, 1, 1], &[]);
+        let half = genome(&things, vec![1, 0, 1, 0, 1, 1], &[]);
+        assert_eq!(a.similarity(&a), 1.0);
+        assert_eq!(a.similarity(&complement), 0.0);
+        assert_eq!(a.similarity(&half), 0.5);
+    }
+
+    #[test]
+    fn injected_mutant_is_a_neighbour_of_the_best() {
+        let things = demo_things();
+        let best = genome(&things, vec![1, 0, 1, 1, 0, 0], &[]);
+        let mut population = population(8, &mut StdRng::seed_from_u64(1));
+        population.data = vec![best.clone(); 8];
+
+        population.inject_best_mutant(&mut StdRng::seed_from_u64(2), 3);
+
+        let moved: Vec<usize> = population
+            .data
+            .iter()
+            .map(|genome| genome.hamming(&best))
+            .filter(|distance| *distance > 0)
+            .collect();
+        assert_eq!(moved, [3]);
+    }
+
+    #[test]
+    fn operator_advances_once_patience_runs_out() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut population = population(8, &mut rng);
+        population.switch_operator_on_stall = true;
+        let config = EvolutionConfig {
+            reset_retain: None,
+            ..Default::default()
+        };
+        let patience = population.state.convergence.threshold;
+
+        population.state.convergence.stagnant = patience;
+        population.after_generation(1, &config, &mut rng);
+        assert_eq!(population.active_operator, 0);
+
+        population.state.convergence.stagnant = patience + 1;
+        population.after_generation(2, &config, &mut rng);
+        assert_eq!(population.active_operator, 1);
+        assert_eq!(population.state.convergence.stagnant, 0);
+    }
+
+    // best value by dynamic programming over the weight alone, like the bound
+    fn dp_optimum(things: &[Thing], limit: u32) -> u32 {
+        let mut best = vec![0; limit as usize + 1];
+        for thing in things {
+            for _ in 0..thing.max_count {
+                for capacity in (thing.weight as usize..best.len()).rev() {
+                    let with = best[capacity - thing.weight as usize] + thing.value;
+                    best[capacity] = best[capacity].max(with);
+                }
+            }
+        }
+        best[limit as usize]
+    }
+
+    #[test]
+    fn fractional_bound_is_at_least_the_dp_optimum() {
+        let things = demo_items();
+        let optimum = dp_optimum(&things, 3000);
+        let bound = fractional_knapsack_bound(&things, 3000);
+        assert_eq!(optimum, 1310);
+        assert!(bound >= optimum as f64, "{bound} < {optimum}");
+    }
+
+    #[test]
+    fn new_with_rng_packs_the_same_items_for_a_seed() {
+        let things: Arc<[Thing]> = demo_items().into();
+        let densities = Initialization::Uniform.densities(&things);
+        let new = |seed| {
+            Genome::new_with_rng(
+                &things,
+                3000,
+                700,
+                FitnessMode::Constrained(ConstraintStrategy::Skip),
+                &[],
+                &densities,
+                &mut StdRng::seed_from_u64(seed),
+            )
+        };
+        assert_eq!(new(42).data, [0, 0, 0, 1, 1, 1, 0, 0, 1, 1]);
+        assert_eq!(new(42).data, new(42).data);
+        assert_ne!(new(42).data, new(43).data);
+    }
 }
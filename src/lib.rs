@@ -0,0 +1,3 @@
+pub mod genetic;
+pub mod knapsack;
+pub mod tsp;
@@ -1,8 +1,75 @@
-mod genetic;
-mod knapsack;
-mod tsp;
+use clap::{Parser, Subcommand};
+use gen_rs::{knapsack, tsp};
+
+#[derive(Parser)]
+#[command(about = "genetic algorithms for knapsack and travelling salesman problems")]
+struct Cli {
+    // random when omitted, and printed so a run can be repeated
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+
+    // runs both problems with their defaults when omitted
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    Knapsack(knapsack::Options),
+    Tsp(tsp::Options),
+}
 
 fn main() {
-    knapsack::run();
-    tsp::run();
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Knapsack(options)) => knapsack::run(cli.seed, &options),
+        Some(Command::Tsp(options)) => tsp::run(cli.seed, &options),
+        None => {
+            knapsack::run(cli.seed, &knapsack::Options::default());
+            tsp::run(cli.seed, &tsp::Options::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_tsp_argv() {
+        let cli = Cli::parse_from([
+            "gen-rs",
+            "--seed",
+            "7",
+            "tsp",
+            "--pop-size",
+            "50",
+            "--generations",
+            "20",
+            "--target",
+            "600",
+            "--input",
+            "data/xqg237.tsp",
+        ]);
+        assert_eq!(cli.seed, Some(7));
+        let Some(Command::Tsp(options)) = cli.command else {
+            panic!("expected the tsp subcommand");
+        };
+        assert_eq!((options.pop_size, options.generations), (50, 20));
+        assert_eq!(options.target, Some(600.0));
+        assert_eq!(options.input, "data/xqg237.tsp");
+    }
+
+    #[test]
+    fn parses_a_knapsack_argv() {
+        let cli = Cli::parse_from(["gen-rs", "knapsack", "--pop-size", "30", "--seed", "3"]);
+        assert_eq!(cli.seed, Some(3));
+        let Some(Command::Knapsack(options)) = cli.command else {
+            panic!("expected the knapsack subcommand");
+        };
+        assert_eq!(options.pop_size, 30);
+        assert_eq!(options.generations, 1000);
+        assert_eq!(options.target, 1310.0);
+    }
 }
@@ -1,17 +1,28 @@
 use core::f64;
 use std::fs::File;
-use std::io::BufRead;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, OnceLock};
 
-use crate::genetic::{Crossover, Fitness, Mutate, Selection};
+use crate::genetic::{
+    self, AdaptiveMutation, Crossover, CutStrategy, EvolutionConfig, Evolve, Fitness, GaRunner,
+    GenerationLimit, Individual, Mutate, Observer, Or, RunFlags, RunResult, RunState, Sense,
+    Similarity, TargetFitness, Termination,
+};
+use rand::rngs::StdRng;
 use rand::seq::{IndexedRandom, SliceRandom};
-use rand::{Rng, rng};
+use rand::{Rng, SeedableRng, rng};
 use textplots::{Chart, Plot, Shape};
 
-#[derive(Debug, Clone, serde::Deserialize)]
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+// for geographic inputs x is the latitude and y the longitude, in degrees
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[allow(dead_code)]
 struct Thing {
     name: String,
+    #[serde(alias = "lat")]
     x: f64,
+    #[serde(alias = "lon")]
     y: f64,
 }
 
@@ -19,70 +30,471 @@ impl Thing {
     fn new(name: String, x: f64, y: f64) -> Self {
         Thing { name, x, y }
     }
+}
+
+trait Distance {
+    fn distance(&self, a: &Thing, b: &Thing) -> f64;
+}
+
+struct Euclidean;
+
+// grid distances, TSPLIB MAN_2D and MAX_2D
+struct Manhattan;
+
+struct Chebyshev;
+
+// great-circle distance in the units of radius, EARTH_RADIUS_KM for kilometers
+struct Haversine {
+    radius: f64,
+}
 
-    fn distance(&self, other: &Thing) -> f64 {
-        let x = self.x - other.x;
-        let y = self.y - other.y;
+impl Distance for Euclidean {
+    fn distance(&self, a: &Thing, b: &Thing) -> f64 {
+        let x = a.x - b.x;
+        let y = a.y - b.y;
         f64::sqrt(x * x + y * y)
     }
 }
 
+impl Distance for Manhattan {
+    fn distance(&self, a: &Thing, b: &Thing) -> f64 {
+        (a.x - b.x).abs() + (a.y - b.y).abs()
+    }
+}
+
+impl Distance for Chebyshev {
+    fn distance(&self, a: &Thing, b: &Thing) -> f64 {
+        (a.x - b.x).abs().max((a.y - b.y).abs())
+    }
+}
+
+impl Distance for Haversine {
+    fn distance(&self, a: &Thing, b: &Thing) -> f64 {
+        let (lat_a, lat_b) = (a.x.to_radians(), b.x.to_radians());
+        let d_lat = lat_b - lat_a;
+        let d_lon = (b.y - a.y).to_radians();
+        let h =
+            (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * self.radius * h.sqrt().asin()
+    }
+}
+
+// the Distance an instance file or --metric picks
+#[derive(Debug, Clone, Copy)]
+pub enum Metric {
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+    Haversine { radius: f64 },
+}
+
+impl Distance for Metric {
+    fn distance(&self, a: &Thing, b: &Thing) -> f64 {
+        match self {
+            Metric::Euclidean => Euclidean.distance(a, b),
+            Metric::Manhattan => Manhattan.distance(a, b),
+            Metric::Chebyshev => Chebyshev.distance(a, b),
+            Metric::Haversine { radius } => Haversine { radius: *radius }.distance(a, b),
+        }
+    }
+}
+
+// euclidean, manhattan, chebyshev, or haversine in kilometers
+impl std::str::FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "euclidean" => Ok(Metric::Euclidean),
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            "haversine" => Ok(Metric::Haversine {
+                radius: EARTH_RADIUS_KM,
+            }),
+            _ => Err(format!(
+                "unknown metric {s}, expected euclidean, manhattan, chebyshev or haversine"
+            )),
+        }
+    }
+}
+
+// every pairwise distance, computed once and shared by all genomes
+#[derive(Debug)]
+struct DistanceMatrix {
+    size: usize,
+    distances: Vec<f64>,
+}
+
+impl DistanceMatrix {
+    fn new(things: &[Thing], metric: impl Distance) -> Self {
+        let distances = things
+            .iter()
+            .flat_map(|a| things.iter().map(|b| metric.distance(a, b)))
+            .collect();
+        Self {
+            size: things.len(),
+            distances,
+        }
+    }
+
+    // lays out the values of a TSPLIB EDGE_WEIGHT_SECTION. a FULL_MATRIX is
+    // kept as given, so it may be asymmetric, while the triangular formats
+    // are mirrored
+    fn from_weights(size: usize, format: &str, weights: &[f64]) -> Result<Self, TspParseError> {
+        let mut distances = vec![0.0; size * size];
+        let mut values = weights.iter().copied();
+        let full = format == "FULL_MATRIX";
+        for i in 0..size {
+            let columns = match format {
+                "FULL_MATRIX" => 0..size,
+                "UPPER_ROW" | "LOWER_COL" => i + 1..size,
+                "LOWER_ROW" | "UPPER_COL" => 0..i,
+                "UPPER_DIAG_ROW" | "LOWER_DIAG_COL" => i..size,
+                "LOWER_DIAG_ROW" | "UPPER_DIAG_COL" => 0..i + 1,
+                _ => return Err(TspParseError::UnsupportedFormat(format.to_string())),
+            };
+            for j in columns {
+                let weight = values
+                    .next()
+                    .ok_or(TspParseError::UnexpectedEof("end of EDGE_WEIGHT_SECTION"))?;
+                distances[i * size + j] = weight;
+                if !full {
+                    distances[j * size + i] = weight;
+                }
+            }
+        }
+
+        Ok(Self { size, distances })
+    }
+
+    fn get(&self, a: usize, b: usize) -> f64 {
+        self.distances[a * self.size + b]
+    }
+
+    fn is_symmetric(&self) -> bool {
+        (0..self.size).all(|a| (0..a).all(|b| self.get(a, b) == self.get(b, a)))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TwoOptMode {
+    FirstImprovement,
+    BestImprovement,
+}
+
+// first or best
+impl std::str::FromStr for TwoOptMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "first" => Ok(TwoOptMode::FirstImprovement),
+            "best" => Ok(TwoOptMode::BestImprovement),
+            _ => Err(format!("unknown 2-opt mode {s}, expected first or best")),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Genome {
     data: Vec<usize>,
-    things: Vec<Thing>,
+    // shared by every genome like the distances, so a clone copies only the tour
+    things: Arc<[Thing]>,
+    distances: Arc<DistanceMatrix>,
+    // tour length, cleared whenever data changes
+    cached_fitness: OnceLock<f64>,
 }
 
 impl Genome {
-    fn new(things: &[Thing]) -> Self {
-        let mut rng = rng();
+    #[allow(dead_code)]
+    fn new_seeded(things: &Arc<[Thing]>, distances: &Arc<DistanceMatrix>, seed: u64) -> Self {
+        Self::new_with_rng(things, distances, &mut StdRng::seed_from_u64(seed))
+    }
+
+    fn new_with_rng<R: Rng + ?Sized>(
+        things: &Arc<[Thing]>,
+        distances: &Arc<DistanceMatrix>,
+        rng: &mut R,
+    ) -> Self {
         let mut data: Vec<usize> = (0..things.len()).collect();
-        data.shuffle(&mut rng);
+        data.shuffle(rng);
+        Self::from_tour(things, distances, data)
+    }
+
+    fn from_tour(things: &Arc<[Thing]>, distances: &Arc<DistanceMatrix>, data: Vec<usize>) -> Self {
         Self {
             data,
-            things: things.to_owned(),
+            things: Arc::clone(things),
+            distances: Arc::clone(distances),
+            cached_fitness: OnceLock::new(),
         }
     }
-}
 
-impl Fitness<f64> for Genome {
-    fn fitness(&self) -> f64 {
+    fn invalidate_fitness(&mut self) {
+        self.cached_fitness = OnceLock::new();
+    }
+
+    fn tour_length(&self) -> f64 {
         let mut distance: f64 = 0.0;
 
-        let mut a = self.things.get(*self.data.first().unwrap()).unwrap();
-        let mut b = self.things.get(*self.data.last().unwrap()).unwrap();
-        distance += a.distance(b);
+        let first = *self.data.first().unwrap();
+        let last = *self.data.last().unwrap();
+        // the closing edge runs from the last city back to the first, which
+        // matters when the matrix is asymmetric
+        distance += self.distances.get(last, first);
 
         for w in self.data.windows(2) {
-            a = self.things.get(*w.to_owned().first().unwrap()).unwrap();
-            b = self.things.get(*w.to_owned().last().unwrap()).unwrap();
-            distance += a.distance(b);
+            distance += self.distances.get(w[0], w[1]);
         }
 
         distance
     }
+
+    // reverse segments while doing so shortens the tour, until no improving
+    // move remains or max_iters moves have been made. moves that would break
+    // one of required_edges are skipped
+    fn two_opt(&mut self, mode: TwoOptMode, max_iters: usize, required_edges: &[(usize, usize)]) {
+        let n = self.data.len();
+        let required = |a: usize, b: usize| {
+            required_edges
+                .iter()
+                .any(|&(x, y)| (x, y) == (a, b) || (y, x) == (a, b))
+        };
+
+        // reversing a segment also reverses every edge inside it, which only
+        // changes its length when the matrix is asymmetric
+        let symmetric = self.distances.is_symmetric();
+        let distance = |a, b| self.distances.get(a, b);
+
+        for _ in 0..max_iters {
+            let mut best_move = None;
+            let mut best_delta = -1e-9;
+
+            'search: for i in 0..n.saturating_sub(1) {
+                // change in length of the edges inside i + 1..=j once reversed
+                let mut reversed = 0.0;
+                for j in i + 2..n {
+                    if !symmetric {
+                        let (x, y) = (self.data[j - 1], self.data[j]);
+                        reversed += distance(y, x) - distance(x, y);
+                    }
+                    if i == 0 && j == n - 1 {
+                        continue;
+                    }
+
+                    let a = self.data[i];
+                    let b = self.data[i + 1];
+                    let c = self.data[j];
+                    let d = self.data[(j + 1) % n];
+                    if required(a, b) || required(c, d) {
+                        continue;
+                    }
+                    let delta = distance(a, c) + distance(b, d) - distance(a, b) - distance(c, d)
+                        + reversed;
+                    if delta < best_delta {
+                        best_delta = delta;
+                        best_move = Some((i, j));
+                        if let TwoOptMode::FirstImprovement = mode {
+                            break 'search;
+                        }
+                    }
+                }
+            }
+
+            match best_move {
+                Some((i, j)) => self.data[i + 1..=j].reverse(),
+                None => break,
+            }
+        }
+        self.invalidate_fitness();
+    }
+
+    // panics naming the operator if the tour is not a permutation of every city
+    fn validate(&self, operator: &str) {
+        let mut seen = vec![false; self.things.len()];
+        for &city in &self.data {
+            assert!(
+                city < seen.len() && !seen[city],
+                "{operator} produced an invalid tour: city {city} is out of range or repeated in {:?}",
+                self.data
+            );
+            seen[city] = true;
+        }
+        assert_eq!(
+            self.data.len(),
+            self.things.len(),
+            "{operator} produced a tour missing cities: {:?}",
+            self.data
+        );
+    }
+
+    // the n longest edges of the closed tour as (from, to, length), longest first
+    #[allow(dead_code)]
+    fn longest_edges(&self, n: usize) -> Vec<(usize, usize, f64)> {
+        let len = self.data.len();
+        let mut edges: Vec<(usize, usize, f64)> = (0..len)
+            .map(|i| {
+                let a = self.data[i];
+                let b = self.data[(i + 1) % len];
+                (a, b, self.distances.get(a, b))
+            })
+            .collect();
+        edges.sort_by(|x, y| y.2.total_cmp(&x.2));
+        edges.truncate(n);
+        edges
+    }
+
+    // rebuild the tour so each chain of required edges is walked in one piece,
+    // placed where the tour first visits any of its cities
+    fn repair_required_edges(&mut self, chains: &[Vec<usize>]) {
+        if chains.is_empty() {
+            return;
+        }
+
+        let mut chain_of = vec![None; self.things.len()];
+        for (c, chain) in chains.iter().enumerate() {
+            for &city in chain {
+                chain_of[city] = Some(c);
+            }
+        }
+
+        let mut placed = vec![false; chains.len()];
+        let mut tour = Vec::with_capacity(self.data.len());
+        for &city in &self.data {
+            match chain_of[city] {
+                None => tour.push(city),
+                Some(c) if !placed[c] => {
+                    placed[c] = true;
+                    let chain = &chains[c];
+                    // enter the chain from whichever end is nearer the previous city
+                    let reversed = tour.last().is_some_and(|&last| {
+                        let distance = |i: usize| self.distances.get(last, i);
+                        distance(*chain.last().unwrap()) < distance(chain[0])
+                    });
+                    if reversed {
+                        tour.extend(chain.iter().rev());
+                    } else {
+                        tour.extend(chain);
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+
+        self.data = tour;
+        self.invalidate_fitness();
+    }
+}
+
+impl Fitness<f64> for Genome {
+    fn fitness(&self) -> f64 {
+        *self.cached_fitness.get_or_init(|| self.tour_length())
+    }
+}
+
+// fraction of tour edges shared with the other tour, in either direction
+impl Similarity for Genome {
+    fn similarity(&self, other: &Self) -> f64 {
+        let n = self.data.len();
+        let mut neighbours = vec![(0, 0); n];
+        other.data.iter().enumerate().for_each(|(i, city)| {
+            neighbours[*city] = (other.data[(i + n - 1) % n], other.data[(i + 1) % n]);
+        });
+
+        let shared = (0..n)
+            .filter(|i| {
+                let (a, b) = (self.data[*i], self.data[(i + 1) % n]);
+                neighbours[a].0 == b || neighbours[a].1 == b
+            })
+            .count();
+
+        shared as f64 / n as f64
+    }
+}
+
+impl Genome {
+    // reverses the tour between two cut points, the same move 2-opt makes
+    fn invert<R: Rng + ?Sized>(&mut self, rng: &mut R, n: usize, prob: f64) {
+        for _ in 0..n {
+            if rng.random_bool(prob) {
+                let mut cuts = rand::seq::index::sample(rng, self.data.len(), 2).into_vec();
+                cuts.sort_unstable();
+                self.data[cuts[0]..=cuts[1]].reverse();
+            }
+        }
+        self.invalidate_fitness();
+    }
+
+    // shuffles the cities between two cut points
+    fn scramble<R: Rng + ?Sized>(&mut self, rng: &mut R, n: usize, prob: f64) {
+        for _ in 0..n {
+            if rng.random_bool(prob) {
+                let mut cuts = rand::seq::index::sample(rng, self.data.len(), 2).into_vec();
+                cuts.sort_unstable();
+                self.data[cuts[0]..=cuts[1]].shuffle(rng);
+            }
+        }
+        self.invalidate_fitness();
+    }
+
+    // moves one city elsewhere in the tour, shifting the cities in between
+    fn insert<R: Rng + ?Sized>(&mut self, rng: &mut R, n: usize, prob: f64) {
+        for _ in 0..n {
+            if rng.random_bool(prob) {
+                let from = rng.random_range(0..self.data.len());
+                let city = self.data.remove(from);
+                let to = rng.random_range(0..=self.data.len());
+                self.data.insert(to, city);
+            }
+        }
+        self.invalidate_fitness();
+    }
 }
 
 impl Mutate for Genome {
-    fn mutate(&mut self, n: usize, prob: f64) {
-        let mut rng = rng();
+    fn mutate<R: Rng + ?Sized>(&mut self, rng: &mut R, n: usize, prob: f64) {
         let mut count = 0;
-        while n < count {
+        while count < n {
             if rng.random_bool(prob) {
-                let index: Vec<usize> = self.data.choose_multiple(&mut rng, 2).cloned().collect();
+                let index: Vec<usize> = self.data.choose_multiple(rng, 2).cloned().collect();
                 self.data
                     .swap(*index.first().unwrap(), *index.last().unwrap());
-                count += 1;
             }
+            count += 1;
         }
+        self.invalidate_fitness();
     }
 }
 
 impl Eq for Genome {}
 
+impl Individual for Genome {
+    const SENSE: Sense = Sense::Minimize;
+}
+
+impl genetic::Genome for Genome {
+    type Gene = usize;
+
+    fn genes(&self) -> &[usize] {
+        &self.data
+    }
+
+    fn random<R: Rng + ?Sized>(&self, rng: &mut R) -> Self {
+        Genome::new_with_rng(&self.things, &self.distances, rng)
+    }
+
+    fn validate(&self, operator: &str) {
+        Genome::validate(self, operator);
+    }
+
+    fn verify(&self) -> bool {
+        self.fitness() == self.tour_length()
+    }
+}
+
 impl Ord for Genome {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.fitness().total_cmp(&other.fitness())
+        Genome::SENSE.compare(self.fitness(), other.fitness())
     }
 }
 
@@ -98,51 +510,302 @@ impl PartialOrd for Genome {
     }
 }
 
-#[derive(Debug, Clone)]
-struct Population {
-    data: Vec<Genome>,
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
+enum CrossoverKind {
+    Prefix,
+    AdaptiveOrder,
+    Order,
+    PartiallyMapped,
+    EdgeRecombination,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MutationKind {
+    Swap,
+    Inversion,
+    Scramble,
+    Insertion,
+}
+
+// swap, inversion, scramble or insertion
+impl std::str::FromStr for MutationKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "swap" => Ok(MutationKind::Swap),
+            "inversion" => Ok(MutationKind::Inversion),
+            "scramble" => Ok(MutationKind::Scramble),
+            "insertion" => Ok(MutationKind::Insertion),
+            _ => Err(format!(
+                "unknown mutation {s}, expected swap, inversion, scramble or insertion"
+            )),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Checkpoint {
     things: Vec<Thing>,
+    distances: Vec<f64>,
+    tours: Vec<Vec<usize>>,
     best: f64,
     generation_since_improvement: usize,
+    evaluations: usize,
+    history: Vec<f64>,
+}
+
+#[derive(Debug, Clone)]
+struct Population {
+    data: Vec<Genome>,
+    things: Arc<[Thing]>,
+    distances: Arc<DistanceMatrix>,
+    state: RunState<Genome>,
+    crossover: CrossoverKind,
+    mutation: MutationKind,
+    // floor on the number of tours parents are drawn from
+    min_breeding_pool: usize,
+    // on stagnation keep the best plus this many dissimilar tours instead of the
+    // reset_retain best
+    diverse_restart: Option<usize>,
+    // polish the best tour with at most this many 2-opt moves each generation
+    two_opt: Option<usize>,
+    two_opt_mode: TwoOptMode,
+    // edges every tour must contain, e.g. a ferry crossing
+    required_edges: Vec<(usize, usize)>,
+    // required_edges joined into paths, worked out when a run starts
+    required_chains: Vec<Vec<usize>>,
 }
 
 impl Population {
-    fn new(pop_size: u32, things: &[Thing]) -> Self {
-        let data = (0..pop_size).map(|_| Genome::new(things)).collect();
+    fn new<R: Rng + ?Sized>(
+        pop_size: u32,
+        things: &[Thing],
+        distances: DistanceMatrix,
+        rng: &mut R,
+    ) -> Self {
+        let things: Arc<[Thing]> = things.into();
+        let distances = Arc::new(distances);
+        let data = (0..pop_size)
+            .map(|_| Genome::new_with_rng(&things, &distances, rng))
+            .collect();
+        Self::with_data(data, things, distances)
+    }
 
+    fn with_data(data: Vec<Genome>, things: Arc<[Thing]>, distances: Arc<DistanceMatrix>) -> Self {
         Self {
+            state: RunState::new(data.len()),
             data,
-            things: things.to_vec(),
-            best: f64::MAX,
-            generation_since_improvement: 0,
+            things,
+            distances,
+            crossover: CrossoverKind::Prefix,
+            mutation: MutationKind::Swap,
+            min_breeding_pool: 2,
+            diverse_restart: None,
+            two_opt: None,
+            two_opt_mode: TwoOptMode::FirstImprovement,
+            required_edges: Vec::new(),
+            required_chains: Vec::new(),
+        }
+    }
+
+    // writes the tours, cities and distances as json so a run can be resumed
+    #[allow(dead_code)]
+    fn save(&self, path: &str) -> std::io::Result<()> {
+        let checkpoint = Checkpoint {
+            things: self.things.to_vec(),
+            distances: self.distances.distances.clone(),
+            tours: self.data.iter().map(|genome| genome.data.clone()).collect(),
+            best: self.state.convergence.best,
+            generation_since_improvement: self.state.convergence.stagnant,
+            evaluations: self.state.evaluations,
+            history: self.state.history.clone(),
+        };
+        serde_json::to_writer(File::create(path)?, &checkpoint)?;
+        Ok(())
+    }
+
+    // options are back to their defaults and need setting again, as after new
+    #[allow(dead_code)]
+    fn load(path: &str) -> std::io::Result<Self> {
+        let checkpoint: Checkpoint = serde_json::from_reader(open_input(path)?)?;
+        let things: Arc<[Thing]> = checkpoint.things.into();
+        let distances = Arc::new(DistanceMatrix {
+            size: things.len(),
+            distances: checkpoint.distances,
+        });
+        let data = checkpoint
+            .tours
+            .into_iter()
+            .map(|tour| Genome::from_tour(&things, &distances, tour))
+            .collect();
+
+        let mut population = Self::with_data(data, things, distances);
+        population.state.convergence.best = checkpoint.best;
+        population.state.convergence.stagnant = checkpoint.generation_since_improvement;
+        population.state.evaluations = checkpoint.evaluations;
+        population.state.history = checkpoint.history;
+        Ok(population)
+    }
+
+    // fraction of positions at which two tours hold different cities, averaged
+    // over every pair of tours. 0 once all tours are identical
+    fn diversity(&self) -> f64 {
+        let n = self.data.len();
+        if n < 2 {
+            return 0.0;
+        }
+
+        let mut total = 0.0;
+        for (i, a) in self.data.iter().enumerate() {
+            for b in &self.data[i + 1..] {
+                let differing = a.data.iter().zip(&b.data).filter(|(x, y)| x != y).count();
+                total += differing as f64 / a.data.len() as f64;
+            }
+        }
+        total / (n * (n - 1) / 2) as f64
+    }
+
+    // shannon entropy of the undirected edge frequencies across all tours
+    fn entropy(&self) -> f64 {
+        let mut counts = std::collections::HashMap::new();
+        for genome in &self.data {
+            let n = genome.data.len();
+            for i in 0..n {
+                let (a, b) = (genome.data[i], genome.data[(i + 1) % n]);
+                *counts.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+
+        let total: usize = counts.values().sum();
+        counts
+            .values()
+            .map(|count| {
+                let p = *count as f64 / total as f64;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    fn random_genomes<R: Rng + ?Sized>(&self, count: usize, rng: &mut R) -> Vec<Genome> {
+        (0..count)
+            .map(|_| Genome::new_with_rng(&self.things, &self.distances, rng))
+            .collect()
+    }
+
+    fn repair_required_edges(&mut self) {
+        for genome in &mut self.data {
+            genome.repair_required_edges(&self.required_chains);
         }
+        self.data.sort()
     }
 
-    fn reset_with_best(&mut self) {
-        self.generation_since_improvement = 0;
-        let mut new = Self::new((self.data.len() - 1) as u32, &self.things);
-        self.data.drain(1..self.data.len());
-        self.data.append(&mut new.data);
+    // keeps the best and then greedily the k tours least similar to those
+    // already kept, regenerating the rest
+    fn reset_keep_diverse<R: Rng + ?Sized>(&mut self, rng: &mut R, k: usize) {
+        self.state.convergence.reset();
+        let size = self.data.len();
+        let mut candidates = std::mem::take(&mut self.data);
+        let mut kept = vec![candidates.remove(0)];
+
+        while kept.len() <= k && !candidates.is_empty() {
+            let (i, _) = candidates
+                .iter()
+                .map(|candidate| {
+                    kept.iter()
+                        .map(|genome| genome.similarity(candidate))
+                        .fold(0.0, f64::max)
+                })
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            kept.push(candidates.swap_remove(i));
+        }
+
+        let mut new = self.random_genomes(size - kept.len(), rng);
+        self.state.evaluations += new.len();
+        self.data = kept;
+        self.data.append(&mut new);
         self.data.sort()
     }
 }
 
-impl Selection for Population {
-    fn selection(&self, size: usize) -> Self {
-        let mut rng = rng();
-        let worst = self.data.last().unwrap().fitness();
-        let data: Vec<Genome> = self
-            .data
-            .choose_multiple_weighted(&mut rng, size, |genome| worst - genome.fitness() + 1.0)
-            .unwrap()
-            .cloned()
-            .collect();
+impl Evolve for Population {
+    type Genome = Genome;
+    type Pair<'a> = Pair<'a>;
 
-        Self {
-            data,
-            things: self.things.clone(),
-            best: f64::MAX,
-            generation_since_improvement: 0,
+    fn genomes(&self) -> &[Genome] {
+        &self.data
+    }
+
+    fn genomes_mut(&mut self) -> &mut Vec<Genome> {
+        &mut self.data
+    }
+
+    fn state(&self) -> &RunState<Genome> {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut RunState<Genome> {
+        &mut self.state
+    }
+
+    fn push_offspring(&mut self, mut genome: Genome) {
+        genome.repair_required_edges(&self.required_chains);
+        self.data.push(genome);
+        self.state.evaluations += 1;
+    }
+
+    fn operator_names(&self) -> (String, String) {
+        (
+            format!("{:?} crossover", self.crossover),
+            format!("{:?} mutation", self.mutation),
+        )
+    }
+
+    fn pair<'a>(&self, a: &'a mut Genome, b: &'a mut Genome, cut: CutStrategy) -> Pair<'a> {
+        Pair {
+            a,
+            b,
+            cut,
+            kind: self.crossover,
+            mutation: self.mutation,
+        }
+    }
+
+    // the better half, but never fewer than min_breeding_pool tours
+    fn breeding_pool(&self) -> usize {
+        (self.data.len() / 2).max(self.min_breeding_pool)
+    }
+
+    fn before_generation<R: Rng + ?Sized>(&mut self, _generation: usize, _rng: &mut R) {
+        if let Some(max_iters) = self.two_opt {
+            let best = self.data.first_mut().unwrap();
+            let before = best.fitness();
+            best.two_opt(self.two_opt_mode, max_iters, &self.required_edges);
+            // the polished tour has to be evaluated again
+            let improved = Genome::SENSE.is_better(best.fitness(), before);
+            self.state.evaluations += 1;
+            self.state.operator_stats.record("2-opt", improved);
+        }
+    }
+
+    fn after_generation<R: Rng + ?Sized>(
+        &mut self,
+        _generation: usize,
+        config: &EvolutionConfig,
+        rng: &mut R,
+    ) {
+        let Some(retain) = config.reset_retain else {
+            return;
+        };
+        if self.state.convergence.converged() {
+            match self.diverse_restart {
+                Some(k) => self.reset_keep_diverse(rng, k),
+                None => self.reset_keep_best(rng, retain),
+            }
+            self.repair_required_edges();
         }
     }
 }
@@ -150,13 +813,15 @@ impl Selection for Population {
 struct Pair<'a> {
     a: &'a mut Genome,
     b: &'a mut Genome,
+    cut: CutStrategy,
+    kind: CrossoverKind,
+    mutation: MutationKind,
 }
 
-impl Crossover for Pair<'_> {
-    fn crossover(&mut self) {
-        let mut rng = rng();
+impl Pair<'_> {
+    fn prefix_crossover<R: Rng + ?Sized>(&mut self, rng: &mut R) {
         let length = self.a.data.len();
-        let cut_point = rng.random_range(0..length);
+        let cut_point = self.cut.cut_point(rng, length);
         let mut new_a = self.a.data[0..cut_point].to_vec();
         let mut new_b = self.b.data[0..cut_point].to_vec();
         self.b.data.iter().for_each(|x| {
@@ -172,118 +837,651 @@ impl Crossover for Pair<'_> {
         self.a.data = new_a;
         self.b.data = new_b;
     }
-}
 
-impl Mutate for Pair<'_> {
-    fn mutate(&mut self, n: usize, prob: f64) {
-        self.a.mutate(n, prob);
-        self.b.mutate(n, prob);
-    }
-}
+    // order crossover with about sqrt(n) cuts; a single cut for small tours
+    fn adaptive_order_crossover<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let length = self.a.data.len();
+        if length < 2 {
+            return;
+        }
 
-fn run_evolution(
-    population: &mut Population,
-    target: f64,
-    generation_limit: usize,
-) -> Option<(&Genome, usize)> {
-    for i in 0..generation_limit {
-        population.data.sort();
+        let cut_count = ((length as f64).sqrt() as usize).saturating_sub(1).max(1);
+        let mut cuts: Vec<usize> = rand::seq::index::sample(rng, length - 1, cut_count)
+            .into_iter()
+            .map(|cut| cut + 1)
+            .collect();
+        cuts.sort();
 
-        if population.best == population.data.first().unwrap().fitness() {
-            population.generation_since_improvement += 1
-        } else if population.data.first().unwrap().fitness() < population.best {
-            population.best = population.data.first().unwrap().fitness();
-            population.generation_since_improvement = 0;
-
-            // plot best fitness
-            plot(population.data.first().unwrap());
-            println!(
-                "generation: {} | population size: {} | best solution so far: {}",
-                i,
-                population.data.len(),
-                population.best,
-            );
-        }
+        // keep every other segment in place, starting with the first
+        let keep: Vec<bool> = (0..length)
+            .map(|i| cuts.iter().filter(|cut| **cut <= i).count() % 2 == 0)
+            .collect();
 
-        if population.generation_since_improvement > 50 {
-            population.reset_with_best();
-        }
+        let new_a = order_fill(&self.a.data, &self.b.data, &keep);
+        let new_b = order_fill(&self.b.data, &self.a.data, &keep);
+        self.a.data = new_a;
+        self.b.data = new_b;
+    }
 
-        // finish cond
-        if population.data.first().unwrap().fitness() <= target && target > 0.0 {
-            return Some((population.data.first().unwrap(), i));
+    // OX: keep the slice between two cuts, then fill from the second cut
+    // onwards (wrapping) with the other parent's cities in its order from that cut
+    fn order_crossover<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let length = self.a.data.len();
+        if length < 2 {
+            return;
         }
 
-        let mut new_population = population.clone();
-        new_population.data = new_population
-            .data
-            .get(0..population.data.len() / 2)
-            .unwrap()
-            .to_vec();
-        for _ in (0..population.data.len()).step_by(4) {
-            let parents = population.selection(2);
-            let mut a = parents.data.first().as_mut().unwrap().clone();
-            let mut b = parents.data.last().as_mut().unwrap().clone();
-            let mut pair = Pair {
-                a: &mut a,
-                b: &mut b,
-            };
-            pair.crossover();
-            pair.mutate(1, 0.5);
-            new_population.data.push(pair.a.to_owned());
-            new_population.data.push(pair.b.to_owned());
+        let mut cuts = rand::seq::index::sample(rng, length + 1, 2).into_vec();
+        cuts.sort();
+        let (start, end) = (cuts[0], cuts[1]);
+
+        let new_a = order_wrap_fill(&self.a.data, &self.b.data, start, end);
+        let new_b = order_wrap_fill(&self.b.data, &self.a.data, start, end);
+        self.a.data = new_a;
+        self.b.data = new_b;
+    }
+
+    // PMX: swap the slice between two cuts and repair duplicates outside it by
+    // following the mapping between the two slices
+    fn pmx<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let length = self.a.data.len();
+        if length < 2 {
+            return;
         }
 
-        *population = new_population;
+        let mut cuts = rand::seq::index::sample(rng, length + 1, 2).into_vec();
+        cuts.sort();
+        let (start, end) = (cuts[0], cuts[1]);
+
+        let new_a = pmx_child(&self.a.data, &self.b.data, start, end);
+        let new_b = pmx_child(&self.b.data, &self.a.data, start, end);
+        self.a.data = new_a;
+        self.b.data = new_b;
     }
 
-    Some((population.data.first().unwrap(), 0))
-}
+    // ERX: each child starts at its own parent's first city and keeps moving to
+    // the unvisited neighbour, from either parent, with the fewest neighbours left
+    fn edge_recombination<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        if self.a.data.len() < 2 {
+            return;
+        }
 
-#[allow(dead_code)]
-fn read_csv(path: &str) -> Vec<Thing> {
-    let mut rdr = csv::Reader::from_path(path).expect("failed to open csv");
-    rdr.deserialize()
-        .map(|result| {
-            let record: Thing = result.unwrap();
-            record
-        })
-        .collect::<Vec<Thing>>()
+        let new_a = erx_child(&self.a.data, &self.b.data, self.a.data[0], rng);
+        let new_b = erx_child(&self.a.data, &self.b.data, self.b.data[0], rng);
+        self.a.data = new_a;
+        self.b.data = new_b;
+    }
 }
 
-#[allow(dead_code)]
-fn read_tsp(path: &str) -> Vec<Thing> {
-    let file = File::open(path).expect("failed to open file");
-    let mut lines = std::io::BufReader::new(file).lines();
-
-    loop {
-        let value = lines.next().unwrap().unwrap();
-        if value.ne("NODE_COORD_SECTION") {
-            continue;
-        } else {
-            break;
+// ties between neighbours are broken at random, and a city left with no
+// unvisited neighbours jumps to a random unvisited city
+fn erx_child<R: Rng + ?Sized>(a: &[usize], b: &[usize], start: usize, rng: &mut R) -> Vec<usize> {
+    let n = a.len();
+    let mut edges: Vec<Vec<usize>> = vec![Vec::with_capacity(4); n];
+    for parent in [a, b] {
+        for i in 0..n {
+            let city = parent[i];
+            for neighbour in [parent[(i + n - 1) % n], parent[(i + 1) % n]] {
+                if !edges[city].contains(&neighbour) {
+                    edges[city].push(neighbour);
+                }
+            }
         }
     }
 
-    let mut triples: Vec<Thing> = vec![];
+    let mut visited = vec![false; n];
+    let mut child = Vec::with_capacity(n);
+    let mut current = start;
     loop {
-        let value = lines.next().unwrap().unwrap();
-        if value.ne("EOF") {
-            let parts: Vec<&str> = value.splitn(3, " ").collect();
-            triples.push(Thing::new(
-                parts.first().unwrap().to_string(),
-                parts.get(1).unwrap().parse::<f64>().unwrap(),
-                parts.last().unwrap().parse::<f64>().unwrap(),
-            ));
-        } else {
-            break;
+        child.push(current);
+        visited[current] = true;
+        if child.len() == n {
+            return child;
         }
+
+        // visited cities are dropped from every list, so these are all unvisited
+        let neighbours = std::mem::take(&mut edges[current]);
+        for neighbour in &neighbours {
+            edges[*neighbour].retain(|city| *city != current);
+        }
+        current = match neighbours.iter().map(|city| edges[*city].len()).min() {
+            Some(fewest) => {
+                let ties: Vec<usize> = neighbours
+                    .into_iter()
+                    .filter(|city| edges[*city].len() == fewest)
+                    .collect();
+                *ties.choose(rng).unwrap()
+            }
+            None => {
+                let unvisited: Vec<usize> = (0..n).filter(|city| !visited[*city]).collect();
+                *unvisited.choose(rng).unwrap()
+            }
+        };
     }
+}
+
+// positions marked in keep come from keep_from, the rest are filled with
+// the remaining cities in the order they appear in fill_from
+fn order_fill(keep_from: &[usize], fill_from: &[usize], keep: &[bool]) -> Vec<usize> {
+    let mut kept = vec![false; keep_from.len()];
+    keep_from
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| **keep)
+        .for_each(|(city, _)| kept[*city] = true);
 
-    triples
+    let mut fill = fill_from.iter().filter(|city| !kept[**city]);
+    keep_from
+        .iter()
+        .zip(keep)
+        .map(|(city, keep)| if *keep { *city } else { *fill.next().unwrap() })
+        .collect()
 }
 
-fn plot(genome: &Genome) {
+// keep_from[start..end] stays in place, the other positions are filled from
+// end onwards with the missing cities in fill_from's order starting at end
+fn order_wrap_fill(
+    keep_from: &[usize],
+    fill_from: &[usize],
+    start: usize,
+    end: usize,
+) -> Vec<usize> {
+    let length = keep_from.len();
+    let mut kept = vec![false; length];
+    keep_from[start..end]
+        .iter()
+        .for_each(|city| kept[*city] = true);
+
+    let mut child = keep_from.to_vec();
+    let mut fill = (0..length)
+        .map(|i| fill_from[(end + i) % length])
+        .filter(|city| !kept[*city]);
+    for i in 0..length - (end - start) {
+        child[(end + i) % length] = fill.next().unwrap();
+    }
+    child
+}
+
+// segment_from[start..end] stays in place and every other position takes the
+// city from other, mapped through the segments until it is not a duplicate
+fn pmx_child(segment_from: &[usize], other: &[usize], start: usize, end: usize) -> Vec<usize> {
+    let length = segment_from.len();
+    let mut position = vec![None; length];
+    for (i, city) in segment_from.iter().enumerate().take(end).skip(start) {
+        position[*city] = Some(i);
+    }
+
+    (0..length)
+        .map(|i| {
+            if (start..end).contains(&i) {
+                return segment_from[i];
+            }
+
+            let mut city = other[i];
+            while let Some(j) = position[city] {
+                city = other[j];
+            }
+            city
+        })
+        .collect()
+}
+
+impl Crossover for Pair<'_> {
+    fn crossover<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        match self.kind {
+            CrossoverKind::Prefix => self.prefix_crossover(rng),
+            CrossoverKind::AdaptiveOrder => self.adaptive_order_crossover(rng),
+            CrossoverKind::Order => self.order_crossover(rng),
+            CrossoverKind::PartiallyMapped => self.pmx(rng),
+            CrossoverKind::EdgeRecombination => self.edge_recombination(rng),
+        }
+        self.a.invalidate_fitness();
+        self.b.invalidate_fitness();
+    }
+}
+
+impl Mutate for Pair<'_> {
+    fn mutate<R: Rng + ?Sized>(&mut self, rng: &mut R, n: usize, prob: f64) {
+        match self.mutation {
+            MutationKind::Swap => {
+                self.a.mutate(rng, n, prob);
+                self.b.mutate(rng, n, prob);
+            }
+            MutationKind::Inversion => {
+                self.a.invert(rng, n, prob);
+                self.b.invert(rng, n, prob);
+            }
+            MutationKind::Scramble => {
+                self.a.scramble(rng, n, prob);
+                self.b.scramble(rng, n, prob);
+            }
+            MutationKind::Insertion => {
+                self.a.insert(rng, n, prob);
+                self.b.insert(rng, n, prob);
+            }
+        }
+    }
+}
+
+// joins required edges into paths. an edge that would give a city more than
+// two required neighbours or close a loop cannot be part of a tour and is dropped
+fn required_chains(cities: usize, edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut neighbours = vec![Vec::new(); cities];
+    let mut root: Vec<usize> = (0..cities).collect();
+    let find = |root: &[usize], mut i: usize| {
+        while root[i] != i {
+            i = root[i];
+        }
+        i
+    };
+
+    for &(a, b) in edges {
+        if a >= cities || b >= cities {
+            eprintln!(
+                "warning: required edge ({a}, {b}) names a city that does not exist, ignoring it"
+            );
+            continue;
+        }
+        let (ra, rb) = (find(&root, a), find(&root, b));
+        if ra == rb || neighbours[a].len() == 2 || neighbours[b].len() == 2 {
+            eprintln!("warning: required edge ({a}, {b}) cannot be part of a tour, ignoring it");
+            continue;
+        }
+        root[ra] = rb;
+        neighbours[a].push(b);
+        neighbours[b].push(a);
+    }
+
+    let mut chains = Vec::new();
+    let mut seen = vec![false; cities];
+    for start in 0..cities {
+        if seen[start] || neighbours[start].len() != 1 {
+            continue;
+        }
+
+        let mut chain = vec![start];
+        seen[start] = true;
+        let mut current = start;
+        while let Some(&next) = neighbours[current].iter().find(|&&city| !seen[city]) {
+            seen[next] = true;
+            chain.push(next);
+            current = next;
+        }
+        chains.push(chain);
+    }
+
+    chains
+}
+
+fn is_degenerate(distances: &DistanceMatrix) -> bool {
+    distances.distances.iter().all(|distance| *distance == 0.0)
+}
+
+#[derive(serde::Serialize)]
+struct Progress<'a> {
+    generation: usize,
+    fitness: f64,
+    tour: &'a [usize],
+}
+
+// plots the best tour and writes a summary to out on improvement, at most
+// once every interval generations, and writes every new best to jsonl. either
+// can be left out
+struct TerminalProgress<'a> {
+    interval: usize,
+    last: Option<usize>,
+    out: Option<&'a mut dyn Write>,
+    jsonl: Option<&'a mut dyn Write>,
+}
+
+impl Observer<Population> for TerminalProgress<'_> {
+    fn on_generation(&mut self, generation: usize, population: &Population, improved: bool) {
+        if !improved {
+            return;
+        }
+
+        if let Some(out) = self.out.as_mut()
+            && self
+                .last
+                .is_none_or(|last| generation - last >= self.interval)
+        {
+            self.last = Some(generation);
+            plot(population.data.first().unwrap(), out).expect("failed to write progress");
+            writeln!(
+                out,
+                "generation: {} | population size: {} | best solution so far: {} | worst: {} | diversity: {:.3}",
+                generation,
+                population.data.len(),
+                population.state.convergence.best,
+                genetic::worst(&population.data).fitness(),
+                population.diversity(),
+            )
+            .expect("failed to write progress");
+        }
+
+        if let Some(out) = self.jsonl.as_mut() {
+            let progress = Progress {
+                generation,
+                fitness: population.state.convergence.best,
+                tour: &population.data.first().unwrap().data,
+            };
+            serde_json::to_writer(&mut *out, &progress).expect("failed to write progress");
+            writeln!(out).expect("failed to write progress");
+            // flushed every line so another process can tail it live
+            out.flush().expect("failed to write progress");
+        }
+    }
+}
+
+fn run_evolution<R: Rng + ?Sized>(
+    population: &mut Population,
+    config: &EvolutionConfig,
+    termination: &dyn Termination,
+    observer: &mut dyn Observer<Population>,
+    rng: &mut R,
+) -> RunResult<Genome, f64> {
+    // every tour has length 0, there is nothing to evolve
+    if is_degenerate(&population.distances) {
+        let best = population.data.first().unwrap().fitness();
+        population.state.history.push(best);
+        return population.result(0, termination.reached_target(best));
+    }
+
+    prepare(population);
+    genetic::run_evolution(population, config, termination, observer, rng)
+}
+
+// joins the required edges into chains and puts them in every tour
+fn prepare(population: &mut Population) {
+    population.required_chains =
+        required_chains(population.things.len(), &population.required_edges);
+    population.repair_required_edges();
+}
+
+// "-" reads from stdin so instances can be piped in
+fn open_input(path: &str) -> std::io::Result<Box<dyn BufRead>> {
+    if path == "-" {
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    Ok(Box::new(std::io::BufReader::new(File::open(path)?)))
+}
+
+// a .csv path is read as csv and any other path as TSPLIB. stdin has no
+// extension, so its format is told from the first line
+// a csv instance is measured with metric when given, otherwise with the one
+// its header implies
+fn read_instance(
+    path: &str,
+    metric: Option<Metric>,
+) -> Result<(Vec<Thing>, DistanceMatrix), TspParseError> {
+    if path == "-" {
+        return parse_instance(std::io::stdin().lock(), metric);
+    }
+    if path.ends_with(".csv") {
+        let (things, implied) = read_csv(path)?;
+        let distances = DistanceMatrix::new(&things, metric.unwrap_or(implied));
+        return Ok((things, distances));
+    }
+    read_tsp(path)
+}
+
+// a csv header line has commas and no "KEY : value" colon
+fn parse_instance<R: BufRead>(
+    mut input: R,
+    metric: Option<Metric>,
+) -> Result<(Vec<Thing>, DistanceMatrix), TspParseError> {
+    let buffered = input.fill_buf()?;
+    let first_line = buffered
+        .split(|byte| *byte == b'\n')
+        .next()
+        .unwrap_or_default();
+    if first_line.contains(&b',') && !first_line.contains(&b':') {
+        let (things, implied) = parse_csv(input)?;
+        let distances = DistanceMatrix::new(&things, metric.unwrap_or(implied));
+        return Ok((things, distances));
+    }
+    parse_tsp(input)
+}
+
+// lat/lon columns mark the coordinates as geographic
+fn read_csv(path: &str) -> Result<(Vec<Thing>, Metric), TspParseError> {
+    parse_csv(open_input(path)?)
+}
+
+fn parse_csv<R: std::io::Read>(input: R) -> Result<(Vec<Thing>, Metric), TspParseError> {
+    let mut rdr = csv::Reader::from_reader(input);
+    let geographic = rdr.headers()?.iter().any(|header| header == "lat");
+    let metric = if geographic {
+        Metric::Haversine {
+            radius: EARTH_RADIUS_KM,
+        }
+    } else {
+        Metric::Euclidean
+    };
+    let things = rdr.deserialize().collect::<Result<Vec<Thing>, _>>()?;
+
+    Ok((things, metric))
+}
+
+#[derive(Debug)]
+enum TspParseError {
+    Io(std::io::Error),
+    Csv(csv::Error),
+    UnexpectedEof(&'static str),
+    UnsupportedFormat(String),
+    UnsupportedWeightType(String),
+    MissingField { line: usize, field: &'static str },
+    InvalidNumber { line: usize, value: String },
+    DimensionMismatch { expected: usize, found: usize },
+}
+
+impl std::fmt::Display for TspParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TspParseError::Io(err) => write!(f, "failed to read tsp file: {err}"),
+            TspParseError::UnexpectedEof(expected) => {
+                write!(f, "unexpected EOF before {expected}")
+            }
+            TspParseError::Csv(err) => write!(f, "failed to read csv file: {err}"),
+            TspParseError::UnsupportedFormat(format) => {
+                write!(f, "unsupported EDGE_WEIGHT_FORMAT {format}")
+            }
+            TspParseError::UnsupportedWeightType(kind) => {
+                write!(f, "unsupported EDGE_WEIGHT_TYPE {kind}")
+            }
+            TspParseError::MissingField { line, field } => {
+                write!(f, "line {line}: missing {field}")
+            }
+            TspParseError::InvalidNumber { line, value } => {
+                write!(f, "line {line}: {value:?} is not a number")
+            }
+            TspParseError::DimensionMismatch { expected, found } => {
+                write!(f, "DIMENSION is {expected} but {found} cities were listed")
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for TspParseError {
+    fn from(err: std::io::Error) -> Self {
+        TspParseError::Io(err)
+    }
+}
+
+impl From<csv::Error> for TspParseError {
+    fn from(err: csv::Error) -> Self {
+        TspParseError::Csv(err)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(line: usize, value: &str) -> Result<T, TspParseError> {
+    value.parse().map_err(|_| TspParseError::InvalidNumber {
+        line,
+        value: value.to_string(),
+    })
+}
+
+// coordinates come from NODE_COORD_SECTION (or DISPLAY_DATA_SECTION, used
+// only for plotting when the instance has explicit edge weights)
+fn read_tsp(path: &str) -> Result<(Vec<Thing>, DistanceMatrix), TspParseError> {
+    parse_tsp(open_input(path)?)
+}
+
+fn parse_tsp<R: BufRead>(input: R) -> Result<(Vec<Thing>, DistanceMatrix), TspParseError> {
+    let mut dimension = None;
+    let mut format = String::from("FULL_MATRIX");
+    let mut metric = Metric::Euclidean;
+    let mut section = None;
+    let mut things: Vec<Thing> = vec![];
+    let mut weights: Vec<f64> = vec![];
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        let number = index + 1;
+        let value = line.trim();
+        match value {
+            "EOF" => return finish_tsp(things, weights, dimension, &format, metric),
+            "NODE_COORD_SECTION" | "DISPLAY_DATA_SECTION" | "EDGE_WEIGHT_SECTION" => {
+                section = Some(value.to_string());
+                continue;
+            }
+            _ => {}
+        }
+
+        if let Some((key, header)) = value.split_once(':') {
+            match key.trim() {
+                "DIMENSION" => {
+                    let size = parse_number(number, header.trim())?;
+                    things.reserve(size);
+                    dimension = Some(size);
+                }
+                "EDGE_WEIGHT_FORMAT" => format = header.trim().to_string(),
+                "EDGE_WEIGHT_TYPE" => {
+                    // EXPLICIT takes its distances from EDGE_WEIGHT_SECTION
+                    metric = match header.trim() {
+                        "EUC_2D" | "EXPLICIT" => Metric::Euclidean,
+                        "MAN_2D" => Metric::Manhattan,
+                        "MAX_2D" => Metric::Chebyshev,
+                        kind => return Err(TspParseError::UnsupportedWeightType(kind.to_string())),
+                    }
+                }
+                _ => {}
+            }
+            section = None;
+            continue;
+        }
+
+        match section.as_deref() {
+            Some("EDGE_WEIGHT_SECTION") => {
+                for weight in value.split_whitespace() {
+                    weights.push(parse_number(number, weight)?);
+                }
+            }
+            Some(_) => {
+                if value.is_empty() {
+                    continue;
+                }
+                let mut parts = value.split_whitespace();
+                let name = parts.next().unwrap();
+                let x = parts.next().ok_or(TspParseError::MissingField {
+                    line: number,
+                    field: "x coordinate",
+                })?;
+                let y = parts.next().ok_or(TspParseError::MissingField {
+                    line: number,
+                    field: "y coordinate",
+                })?;
+                things.push(Thing::new(
+                    name.to_string(),
+                    parse_number(number, x)?,
+                    parse_number(number, y)?,
+                ));
+            }
+            None => {}
+        }
+    }
+
+    if things.is_empty() && weights.is_empty() {
+        return Err(TspParseError::UnexpectedEof("NODE_COORD_SECTION"));
+    }
+    Err(TspParseError::UnexpectedEof("EOF marker"))
+}
+
+// explicit weights win over coordinates; cities without coordinates all sit at
+// the origin. any coordinates given must cover exactly DIMENSION cities
+fn finish_tsp(
+    mut things: Vec<Thing>,
+    weights: Vec<f64>,
+    dimension: Option<usize>,
+    format: &str,
+    metric: Metric,
+) -> Result<(Vec<Thing>, DistanceMatrix), TspParseError> {
+    if let Some(size) = dimension
+        && !things.is_empty()
+        && things.len() != size
+    {
+        return Err(TspParseError::DimensionMismatch {
+            expected: size,
+            found: things.len(),
+        });
+    }
+
+    if weights.is_empty() {
+        if things.is_empty() {
+            return Err(TspParseError::UnexpectedEof("NODE_COORD_SECTION"));
+        }
+        let distances = DistanceMatrix::new(&things, metric);
+        return Ok((things, distances));
+    }
+
+    let size = dimension.ok_or(TspParseError::UnexpectedEof("DIMENSION"))?;
+    let distances = DistanceMatrix::from_weights(size, format, &weights)?;
+    if things.is_empty() {
+        things = (1..=size)
+            .map(|i| Thing::new(i.to_string(), 0.0, 0.0))
+            .collect();
+    }
+    Ok((things, distances))
+}
+
+// n x n matrix with city names as the header row and first column
+fn write_distance_matrix(
+    things: &[Thing],
+    distances: &DistanceMatrix,
+    path: &str,
+) -> csv::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    let header = std::iter::once("").chain(things.iter().map(|thing| thing.name.as_str()));
+    writer.write_record(header)?;
+
+    for (a, thing) in things.iter().enumerate() {
+        let row = std::iter::once(thing.name.clone())
+            .chain((0..things.len()).map(|b| distances.get(a, b).to_string()));
+        writer.write_record(row)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+// best tour length per generation
+fn plot_history(history: &[f64], out: &mut dyn Write) -> std::io::Result<()> {
+    let points: Vec<(f32, f32)> = history
+        .iter()
+        .enumerate()
+        .map(|(generation, fitness)| (generation as f32, *fitness as f32))
+        .collect();
+
+    let line = Shape::Lines(points.as_slice());
+    let mut chart = Chart::new(320, 140, 0.0, history.len() as f32);
+    let chart = chart.lineplot(&line);
+    chart.axis();
+    chart.figures();
+    writeln!(out, "{chart}")
+}
+
+// city coordinates in tour order, ending back at the first city
+fn tour_points(genome: &Genome) -> Vec<(f32, f32)> {
     let mut tuples: Vec<(f32, f32)> = vec![];
     genome.data.iter().for_each(|id| {
         let things = genome.things.get(*id).unwrap();
@@ -291,6 +1489,11 @@ fn plot(genome: &Genome) {
     });
     let last_pt = genome.things.get(*genome.data.first().unwrap()).unwrap();
     tuples.push((last_pt.x as f32, last_pt.y as f32));
+    tuples
+}
+
+fn plot(genome: &Genome, out: &mut dyn Write) -> std::io::Result<()> {
+    let tuples = tour_points(genome);
 
     let min_x = tuples
         .iter()
@@ -303,18 +1506,1237 @@ fn plot(genome: &Genome) {
         .unwrap()
         .0;
 
-    print!("{}[2J", 27 as char);
-    Chart::new(320, 140, min_x, max_x)
-        .lineplot(&Shape::Points(tuples.as_slice()))
-        .lineplot(&Shape::Lines(tuples.as_slice()))
-        .display();
+    write!(out, "{}[2J", 27 as char)?;
+    let (cities, tour) = (Shape::Points(&tuples), Shape::Lines(&tuples));
+    let mut chart = Chart::new(320, 140, min_x, max_x);
+    let chart = chart.lineplot(&cities).lineplot(&tour);
+    chart.axis();
+    chart.figures();
+    writeln!(out, "{chart}")
 }
 
-pub fn run() {
-    let things = read_tsp("data/xqf131.tsp");
-    // let things = read_csv("data/uk-cities.csv");
-    let mut population = Population::new(500, &things);
-    let solution = run_evolution(&mut population, 0.0, 10000).expect("no solution found");
-    plot(solution.0);
-    println!("solution: {} - {:?}", solution.0.fitness(), solution.0.data);
+// the tour as an svg of its cities and edges, scaled to the coordinate bounds
+#[allow(dead_code)]
+fn plot_to_file(genome: &Genome, path: &str) -> std::io::Result<()> {
+    const SIZE: f32 = 800.0;
+    const MARGIN: f32 = 20.0;
+
+    let points = tour_points(genome);
+    let (min_x, max_x) = points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| {
+        (lo.min(p.0), hi.max(p.0))
+    });
+    let (min_y, max_y) = points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| {
+        (lo.min(p.1), hi.max(p.1))
+    });
+    let scale = (SIZE - 2.0 * MARGIN) / (max_x - min_x).max(max_y - min_y).max(f32::EPSILON);
+    // svg y grows downwards, so flip it to keep north up
+    let project = |(x, y): (f32, f32)| {
+        (
+            MARGIN + (x - min_x) * scale,
+            SIZE - MARGIN - (y - min_y) * scale,
+        )
+    };
+
+    let mut out = std::io::BufWriter::new(File::create(path)?);
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{SIZE}" height="{SIZE}" viewBox="0 0 {SIZE} {SIZE}">"#
+    )?;
+    writeln!(out, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+    let path_points: Vec<String> = points
+        .iter()
+        .map(|point| {
+            let (x, y) = project(*point);
+            format!("{x:.1},{y:.1}")
+        })
+        .collect();
+    writeln!(
+        out,
+        r#"<polyline points="{}" fill="none" stroke="steelblue" stroke-width="1.5"/>"#,
+        path_points.join(" ")
+    )?;
+    for point in &points[..points.len() - 1] {
+        let (x, y) = project(*point);
+        writeln!(
+            out,
+            r#"<circle cx="{x:.1}" cy="{y:.1}" r="3" fill="black"/>"#
+        )?;
+    }
+    writeln!(out, "</svg>")?;
+    out.flush()
+}
+
+// the tour in TSPLIB .tour format, named after the file. TSPLIB nodes count from 1
+#[allow(dead_code)]
+fn write_tour(genome: &Genome, path: &str) -> std::io::Result<()> {
+    let name = std::path::Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("tour");
+
+    let mut out = std::io::BufWriter::new(File::create(path)?);
+    writeln!(out, "NAME : {name}")?;
+    writeln!(out, "COMMENT : length {}", genome.fitness())?;
+    writeln!(out, "TYPE : TOUR")?;
+    writeln!(out, "DIMENSION : {}", genome.data.len())?;
+    writeln!(out, "TOUR_SECTION")?;
+    for id in &genome.data {
+        writeln!(out, "{}", id + 1)?;
+    }
+    writeln!(out, "-1")?;
+    writeln!(out, "EOF")?;
+    out.flush()
+}
+
+pub const DEFAULT_INPUT: &str = "data/xqf131.tsp";
+
+// best known tour length for data/xqf131.tsp
+const XQF131_OPTIMUM: f64 = 564.0;
+
+// what run() and solve() do, parsed from the tsp subcommand
+#[derive(Debug, Clone, clap::Parser)]
+pub struct Options {
+    #[arg(long, default_value_t = 500)]
+    pub pop_size: u32,
+    #[arg(long, default_value_t = 10000)]
+    pub generations: usize,
+    // stop once a tour is at least this short
+    #[arg(long)]
+    pub target: Option<f64>,
+    // a TSPLIB .tsp file, or a .csv with name,x,y or name,lat,lon columns
+    #[arg(long, default_value = DEFAULT_INPUT)]
+    pub input: String,
+    // euclidean, manhattan, chebyshev or haversine for a csv input, instead
+    // of haversine for lat,lon columns and euclidean otherwise
+    #[arg(long)]
+    pub metric: Option<Metric>,
+    // write every new best as a json line to this file, - for stdout
+    #[arg(long)]
+    pub jsonl: Option<String>,
+    // on stagnation keep the best plus this many dissimilar tours rather
+    // than only the best few
+    #[arg(long)]
+    pub diverse_restart: Option<usize>,
+    // edges every tour must contain as pairs of city indices, e.g. 3-17,4-9
+    #[arg(long, value_parser = parse_edge, value_delimiter = ',')]
+    pub required_edges: Vec<(usize, usize)>,
+    // swap, inversion, scramble or insertion
+    #[arg(long, default_value = "inversion")]
+    pub mutation: MutationKind,
+    // polish the best tour with at most this many 2-opt moves each
+    // generation, 0 to turn 2-opt off
+    #[arg(long, default_value_t = 100)]
+    pub two_opt: usize,
+    // first to take each improving 2-opt move as found, best to scan for the
+    // biggest one first
+    #[arg(long, default_value = "first")]
+    pub two_opt_mode: TwoOptMode,
+    // write the instance's distance matrix to this csv file before the run
+    #[arg(long)]
+    pub dump_distance_matrix: Option<String>,
+    // redraw the progress plot at most once per this many generations
+    #[arg(long, default_value_t = 10)]
+    pub progress_interval: usize,
+    #[command(flatten)]
+    pub run: RunFlags,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        clap::Parser::parse_from(["tsp"])
+    }
+}
+
+fn parse_edge(s: &str) -> Result<(usize, usize), String> {
+    let (a, b) = s
+        .split_once('-')
+        .ok_or_else(|| format!("expected an edge like 3-17, got {s}"))?;
+    let city = |i: &str| i.parse().map_err(|_| format!("invalid city index {i}"));
+    Ok((city(a)?, city(b)?))
+}
+
+// the population and config run() uses for an instance
+fn setup<R: Rng + ?Sized>(options: &Options, rng: &mut R) -> (Population, EvolutionConfig) {
+    let (things, distances) =
+        read_instance(&options.input, options.metric).unwrap_or_else(|err| panic!("{err}"));
+    let mut population = Population::new(options.pop_size, &things, distances, rng);
+    population.crossover = CrossoverKind::AdaptiveOrder;
+    population.mutation = options.mutation;
+    population.two_opt = (options.two_opt > 0).then_some(options.two_opt);
+    population.two_opt_mode = options.two_opt_mode;
+    population.diverse_restart = options.diverse_restart;
+    population.required_edges = options.required_edges.clone();
+    // keep the better half of the tours each generation
+    let mut config = EvolutionConfig {
+        elite_count: options.pop_size as usize / 2,
+        adaptive_mutation: Some(AdaptiveMutation {
+            max_prob: 1.0,
+            ramp: 50,
+        }),
+        operator_stats: true,
+        ..Default::default()
+    };
+    options.run.apply(&mut config);
+    (population, config)
+}
+
+// the generation limit, or'd with the target when there is one
+fn termination(options: &Options) -> Box<dyn Termination> {
+    let generations = GenerationLimit(options.generations);
+    match options.target {
+        Some(target) => Box::new(Or(
+            TargetFitness {
+                target,
+                sense: Genome::SENSE,
+            },
+            generations,
+        )),
+        None => Box::new(generations),
+    }
+}
+
+// a run() stepped one generation at a time, for interactive viewers
+pub struct Runner {
+    runner: GaRunner<Population, StdRng>,
+    finished: bool,
+}
+
+impl Runner {
+    pub fn new(seed: u64, options: &Options) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (mut population, config) = setup(options, &mut rng);
+        prepare(&mut population);
+        Self {
+            runner: GaRunner::new(population, config, termination(options), rng),
+            finished: false,
+        }
+    }
+
+    // runs one generation, false once the run is over
+    pub fn step(&mut self) -> bool {
+        if !self.finished {
+            let mut quiet = |_: usize, _: &Population, _: bool| {};
+            self.finished = self.runner.step(&mut quiet).is_some();
+        }
+        !self.finished
+    }
+
+    pub fn generation(&self) -> usize {
+        self.runner.generation()
+    }
+
+    pub fn best_length(&self) -> f64 {
+        self.best().fitness()
+    }
+
+    // plots the best tour so far
+    pub fn plot(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        plot(self.best(), out)
+    }
+
+    // offspring are only sorted in at the start of the next generation
+    fn best(&self) -> &Genome {
+        self.runner.population().data.iter().min().unwrap()
+    }
+}
+
+// run() without any output, returning the length of the best tour found
+pub fn solve(seed: u64, options: &Options) -> f64 {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (mut population, config) = setup(options, &mut rng);
+    run_evolution(
+        &mut population,
+        &config,
+        &GenerationLimit(options.generations),
+        &mut |_: usize, _: &Population, _: bool| {},
+        &mut rng,
+    )
+    .fitness
+}
+
+pub fn run(seed: Option<u64>, options: &Options) {
+    let jsonl = options.jsonl.as_deref();
+    // with the json lines on stdout everything meant for a person goes to stderr
+    let mut out: Box<dyn Write> = if jsonl == Some("-") {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    };
+    let seed = seed.unwrap_or_else(|| rng().random());
+    writeln!(out, "tsp seed: {seed}").expect("failed to write the report");
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let (mut population, config) = setup(options, &mut rng);
+    if let Some(path) = &options.dump_distance_matrix {
+        write_distance_matrix(&population.things, &population.distances, path)
+            .unwrap_or_else(|err| panic!("{path}: {err}"));
+    }
+    let termination = termination(options);
+    let mut jsonl_out: Option<Box<dyn Write>> = jsonl.map(|path| -> Box<dyn Write> {
+        if path == "-" {
+            Box::new(std::io::stdout())
+        } else {
+            let file = File::create(path).unwrap_or_else(|err| panic!("{path}: {err}"));
+            Box::new(std::io::BufWriter::new(file))
+        }
+    });
+    let mut progress = TerminalProgress {
+        interval: options.progress_interval,
+        last: None,
+        out: Some(&mut *out),
+        jsonl: jsonl_out.as_mut().map(|out| out as &mut dyn Write),
+    };
+    let solution = run_evolution(
+        &mut population,
+        &config,
+        termination.as_ref(),
+        &mut progress,
+        &mut rng,
+    );
+    debug_assert!(
+        genetic::Genome::verify(&solution.best),
+        "stale fitness cache on the best genome"
+    );
+    report(&solution, &population, options, &mut *out).expect("failed to write the report");
+}
+
+// the best tour and what the run took to find it, ending with a plot of the
+// best length over the generations
+fn report(
+    solution: &RunResult<Genome, f64>,
+    population: &Population,
+    options: &Options,
+    out: &mut dyn Write,
+) -> std::io::Result<()> {
+    if is_degenerate(&population.distances) {
+        writeln!(
+            out,
+            "every distance between cities is 0, every tour has length 0"
+        )?;
+    }
+    plot(&solution.best, out)?;
+    writeln!(
+        out,
+        "solution: {} - {:?} - met target: {}",
+        solution.fitness, solution.best.data, solution.met_target
+    )?;
+    if options.input == DEFAULT_INPUT {
+        writeln!(
+            out,
+            "optimality gap: {:.2}%",
+            Genome::SENSE.optimality_gap(solution.fitness, XQF131_OPTIMUM)
+        )?;
+    }
+    writeln!(out, "evaluations: {}", solution.metrics.evaluations)?;
+    writeln!(out, "entropy: {:.3}", population.entropy())?;
+    write!(out, "{}", solution.metrics.operator_stats)?;
+    plot_history(&solution.history, out)
+}
+
+// entry points for benches/, which can't reach the private types above
+pub mod bench {
+    use super::*;
+
+    #[derive(Clone)]
+    pub struct Fixture {
+        population: Population,
+        config: EvolutionConfig,
+        rng: StdRng,
+    }
+
+    impl Fixture {
+        // the population and config run() would use on an instance, already evaluated
+        pub fn load(path: &str, pop_size: u32, seed: u64) -> Self {
+            let options = Options {
+                input: path.to_string(),
+                pop_size,
+                ..Default::default()
+            };
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (population, config) = setup(&options, &mut rng);
+            population.evaluate();
+            Self {
+                population,
+                config,
+                rng,
+            }
+        }
+
+        // length of the first tour, worked out again rather than read from the cache
+        pub fn fitness(&mut self) -> f64 {
+            let genome = self.population.data.first_mut().unwrap();
+            genome.invalidate_fitness();
+            genome.fitness()
+        }
+
+        pub fn selection(&mut self, size: usize) -> usize {
+            let population = &self.population;
+            genetic::select(
+                &population.data,
+                self.config.selection,
+                population.state.temperature,
+                &mut self.rng,
+                size,
+            )
+            .len()
+        }
+
+        // breeds a single generation and returns the best fitness after it
+        pub fn generation(&mut self) -> f64 {
+            run_evolution(
+                &mut self.population,
+                &self.config,
+                &GenerationLimit(1),
+                &mut |_: usize, _: &Population, _: bool| {},
+                &mut self.rng,
+            )
+            .fitness
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // unit square, 0 and 2 are opposite corners
+    fn square() -> (Arc<[Thing]>, Arc<DistanceMatrix>) {
+        let things: Arc<[Thing]> = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+            .iter()
+            .enumerate()
+            .map(|(i, (x, y))| Thing::new(i.to_string(), *x, *y))
+            .collect();
+        let distances = Arc::new(DistanceMatrix::new(&things, Metric::Euclidean));
+        (things, distances)
+    }
+
+    fn has_edge(tour: &[usize], a: usize, b: usize) -> bool {
+        (0..tour.len()).any(|i| {
+            let (x, y) = (tour[i], tour[(i + 1) % tour.len()]);
+            (x, y) == (a, b) || (y, x) == (a, b)
+        })
+    }
+
+    const CSV: &str = "name,x,y\na,0,0\nb,3,4\nc,6,0\n";
+    const TSP: &str = "NAME : tiny\nTYPE : TSP\nDIMENSION : 3\nEDGE_WEIGHT_TYPE : EUC_2D\nNODE_COORD_SECTION\n1 0 0\n2 3 4\n3 6 0\nEOF\n";
+
+    #[test]
+    fn unique_elites_skips_repeated_tours() {
+        let (things, distances) = square();
+        let around = Genome::from_tour(&things, &distances, vec![0, 1, 2, 3]);
+        let crossed = Genome::from_tour(&things, &distances, vec![0, 2, 1, 3]);
+        let genomes = vec![around.clone(), around, crossed.clone()];
+        let elites = genetic::unique_elites(&genomes, 2);
+        assert_eq!(elites.len(), 2);
+        assert_eq!(elites[1].data, crossed.data);
+    }
+
+    #[test]
+    fn parse_csv_reads_from_a_cursor() {
+        let (things, _) = parse_csv(std::io::Cursor::new(CSV)).unwrap();
+        let names: Vec<&str> = things.iter().map(|thing| thing.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+        assert_eq!((things[1].x, things[1].y), (3.0, 4.0));
+    }
+
+    #[test]
+    fn parse_tsp_reads_from_a_cursor() {
+        let (things, distances) = parse_tsp(std::io::Cursor::new(TSP)).unwrap();
+        assert_eq!(things.len(), 3);
+        assert_eq!(distances.get(0, 1), 5.0);
+    }
+
+    #[test]
+    fn parse_instance_tells_csv_from_tsplib() {
+        for input in [CSV, TSP] {
+            let (things, distances) = parse_instance(std::io::Cursor::new(input), None).unwrap();
+            assert_eq!(things.len(), 3);
+            assert_eq!(distances.get(1, 2), 5.0);
+        }
+    }
+
+    #[test]
+    fn jsonl_progress_writes_a_line_per_improvement() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let (things, distances) =
+            parse_tsp(std::io::BufReader::new(File::open(DEFAULT_INPUT).unwrap())).unwrap();
+        let mut population = Population::new(30, &things, distances, &mut rng);
+        let config = EvolutionConfig {
+            record_best_genomes: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        let mut progress = TerminalProgress {
+            interval: 10,
+            last: None,
+            out: None,
+            jsonl: Some(&mut out),
+        };
+        let result = run_evolution(
+            &mut population,
+            &config,
+            &GenerationLimit(30),
+            &mut progress,
+            &mut rng,
+        );
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(result.best_genomes.len() > 1);
+        assert_eq!(lines.len(), result.best_genomes.len());
+        for (line, best) in lines.iter().zip(&result.best_genomes) {
+            assert!((line["fitness"].as_f64().unwrap() - best.fitness()).abs() < 1e-9);
+            assert_eq!(line["tour"].as_array().unwrap().len(), things.len());
+            assert!(line["generation"].is_u64());
+        }
+    }
+
+    #[test]
+    fn verify_catches_a_stale_cache() {
+        let (things, distances) = square();
+        let mut genome = Genome::from_tour(&things, &distances, vec![0, 1, 2, 3]);
+        assert!(genetic::Genome::verify(&genome));
+
+        genome.data.swap(1, 2);
+        assert!(!genetic::Genome::verify(&genome));
+        genome.invalidate_fitness();
+        assert!(genetic::Genome::verify(&genome));
+    }
+
+    #[test]
+    fn full_matrix_keeps_asymmetric_weights() {
+        let input = "NAME : atsp\nTYPE : ATSP\nDIMENSION : 3\nEDGE_WEIGHT_TYPE : EXPLICIT\nEDGE_WEIGHT_FORMAT : FULL_MATRIX\nEDGE_WEIGHT_SECTION\n0 1 2\n3 0 4\n5 6 0\nEOF\n";
+        let (things, distances) = parse_tsp(std::io::Cursor::new(input)).unwrap();
+        assert_eq!((distances.get(0, 1), distances.get(1, 0)), (1.0, 3.0));
+        assert_eq!((distances.get(1, 2), distances.get(2, 1)), (4.0, 6.0));
+
+        let genome = Genome::from_tour(&things.into(), &Arc::new(distances), vec![0, 1, 2]);
+        assert_eq!(genome.fitness(), 1.0 + 4.0 + 5.0);
+    }
+
+    #[test]
+    fn upper_row_is_mirrored() {
+        let input = "NAME : tri\nTYPE : TSP\nDIMENSION : 3\nEDGE_WEIGHT_TYPE : EXPLICIT\nEDGE_WEIGHT_FORMAT : UPPER_ROW\nEDGE_WEIGHT_SECTION\n1 2\n4\nEOF\n";
+        let (_, distances) = parse_tsp(std::io::Cursor::new(input)).unwrap();
+        assert_eq!((distances.get(0, 2), distances.get(2, 0)), (2.0, 2.0));
+        assert_eq!((distances.get(1, 2), distances.get(2, 1)), (4.0, 4.0));
+    }
+
+    #[test]
+    fn unsupported_weight_type_is_rejected() {
+        let input = TSP.replace("EUC_2D", "GEO");
+        let err = parse_tsp(std::io::Cursor::new(input)).unwrap_err();
+        assert!(matches!(err, TspParseError::UnsupportedWeightType(kind) if kind == "GEO"));
+    }
+
+    #[test]
+    fn two_opt_untangles_crossing_tour() {
+        let (things, distances) = square();
+        let mut genome = Genome::from_tour(&things, &distances, vec![0, 2, 1, 3]);
+        genome.two_opt(TwoOptMode::BestImprovement, 10, &[]);
+        assert_eq!(genome.fitness(), 4.0);
+    }
+
+    #[test]
+    fn two_opt_keeps_required_edges() {
+        let (things, distances) = square();
+        let mut genome = Genome::from_tour(&things, &distances, vec![0, 2, 1, 3]);
+        genome.two_opt(TwoOptMode::BestImprovement, 10, &[(2, 0)]);
+        assert!(has_edge(&genome.data, 0, 2));
+    }
+
+    fn random_cities(n: usize, seed: u64) -> (Arc<[Thing]>, Arc<DistanceMatrix>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let things: Arc<[Thing]> = (0..n)
+            .map(|i| {
+                let (x, y) = (rng.random_range(0.0..100.0), rng.random_range(0.0..100.0));
+                Thing::new(i.to_string(), x, y)
+            })
+            .collect();
+        let distances = Arc::new(DistanceMatrix::new(&things, Metric::Euclidean));
+        (things, distances)
+    }
+
+    #[test]
+    fn new_seeded_tours_repeat() {
+        let (things, distances) = random_cities(20, 1);
+        let a = Genome::new_seeded(&things, &distances, 42);
+        let b = Genome::new_seeded(&things, &distances, 42);
+        let c = Genome::new_seeded(&things, &distances, 43);
+        assert_eq!(a.data, b.data);
+        assert_ne!(a.data, c.data);
+    }
+
+    fn is_permutation(tour: &[usize]) -> bool {
+        let mut sorted = tour.to_vec();
+        sorted.sort_unstable();
+        sorted == (0..tour.len()).collect::<Vec<usize>>()
+    }
+
+    #[test]
+    fn mutate_swaps_cities_and_keeps_a_permutation() {
+        let (things, distances) = random_cities(10, 1);
+        let mut genome = Genome::from_tour(&things, &distances, (0..10).collect());
+        genome.mutate(&mut StdRng::seed_from_u64(5), 3, 1.0);
+        // three swaps are an odd permutation, so they can never cancel out
+        assert_ne!(genome.data, (0..10).collect::<Vec<usize>>());
+        assert!(is_permutation(&genome.data));
+    }
+
+    #[test]
+    fn two_opt_modes_stop_at_a_local_optimum() {
+        let (things, distances) = random_cities(12, 2);
+        for mode in [TwoOptMode::FirstImprovement, TwoOptMode::BestImprovement] {
+            let mut genome = Genome::new_seeded(&things, &distances, 3);
+            genome.two_opt(mode, usize::MAX, &[]);
+            assert!(is_permutation(&genome.data));
+
+            let tour = &genome.data;
+            let n = tour.len();
+            let distance = |a: usize, b: usize| distances.get(tour[a], tour[b]);
+            for i in 0..n - 1 {
+                for j in i + 2..n {
+                    if i == 0 && j == n - 1 {
+                        continue;
+                    }
+                    let delta = distance(i, j) + distance(i + 1, (j + 1) % n)
+                        - distance(i, i + 1)
+                        - distance(j, (j + 1) % n);
+                    assert!(
+                        delta >= -1e-9,
+                        "{mode:?} left move ({i}, {j}) worth {delta}"
+                    );
+                }
+            }
+        }
+    }
+
+    fn cross(a: &[usize], b: &[usize], kind: CrossoverKind, seed: u64) -> (Vec<usize>, Vec<usize>) {
+        let (things, distances) = random_cities(a.len(), 0);
+        let mut a = Genome::from_tour(&things, &distances, a.to_vec());
+        let mut b = Genome::from_tour(&things, &distances, b.to_vec());
+        let mut pair = Pair {
+            a: &mut a,
+            b: &mut b,
+            cut: CutStrategy::Random,
+            kind,
+            mutation: MutationKind::Swap,
+        };
+        pair.crossover(&mut StdRng::seed_from_u64(seed));
+        (a.data, b.data)
+    }
+
+    #[test]
+    fn order_crossover_keeps_the_middle_of_a() {
+        // the textbook example, counted from 0 and cut at 3..7
+        let a: Vec<usize> = (0..9).collect();
+        let b = vec![8, 2, 6, 7, 1, 5, 4, 0, 3];
+        let child = order_wrap_fill(&a, &b, 3, 7);
+        assert_eq!(child, vec![2, 7, 1, 3, 4, 5, 6, 0, 8]);
+
+        for seed in 0..20 {
+            let (a, b) = cross(&a, &b, CrossoverKind::Order, seed);
+            assert!(is_permutation(&a) && is_permutation(&b));
+        }
+    }
+
+    #[test]
+    fn pmx_follows_the_mapping() {
+        // the textbook example, counted from 0 and cut at 3..7: 3 maps to 0
+        // and 4 to 7 through the segments
+        let a: Vec<usize> = (0..9).collect();
+        let b = vec![3, 4, 1, 0, 7, 6, 5, 8, 2];
+        assert_eq!(pmx_child(&a, &b, 3, 7), vec![0, 7, 1, 3, 4, 5, 6, 8, 2]);
+
+        // a shifted by one, so every conflict resolves through a long chain
+        let b: Vec<usize> = (1..9).chain([0]).collect();
+        for seed in 0..20 {
+            let (a, b) = cross(&a, &b, CrossoverKind::PartiallyMapped, seed);
+            assert!(is_permutation(&a) && is_permutation(&b));
+        }
+    }
+
+    #[test]
+    fn edge_recombination_keeps_shared_edges() {
+        // both parents hold 0-1, 1-2 and 3-4
+        let a = [0, 1, 2, 3, 4];
+        let b = [0, 1, 2, 4, 3];
+        for seed in 0..20 {
+            let (x, y) = cross(&a, &b, CrossoverKind::EdgeRecombination, seed);
+            for child in [x, y] {
+                assert!(is_permutation(&child));
+                for (from, to) in [(0, 1), (1, 2), (3, 4)] {
+                    assert!(has_edge(&child, from, to), "{child:?} lost {from}-{to}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn longest_edges_are_sorted_longest_first() {
+        let (things, _) = parse_csv(std::io::Cursor::new(CSV)).unwrap();
+        let distances = Arc::new(DistanceMatrix::new(&things, Metric::Euclidean));
+        let genome = Genome::from_tour(&things.into(), &distances, vec![0, 1, 2]);
+        let edges = genome.longest_edges(2);
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0], (2, 0, 6.0));
+        assert_eq!(edges[1].2, 5.0);
+    }
+
+    // a file in the temp dir unique to this test process
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("gen-rs-{}-{name}", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn write_tour_round_trips() {
+        let (things, distances) = random_cities(8, 4);
+        let genome = Genome::new_seeded(&things, &distances, 4);
+        let path = temp_path("round-trip.tour");
+        write_tour(&genome, &path).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(text.contains("TYPE : TOUR"));
+        assert!(text.contains("DIMENSION : 8"));
+        let tour: Vec<usize> = text
+            .lines()
+            .skip_while(|line| *line != "TOUR_SECTION")
+            .skip(1)
+            .take_while(|line| *line != "-1")
+            .map(|line| line.parse::<usize>().unwrap() - 1)
+            .collect();
+        assert_eq!(tour, genome.data);
+    }
+
+    #[test]
+    fn plot_to_file_writes_an_svg() {
+        let (things, distances) = random_cities(8, 5);
+        let genome = Genome::new_seeded(&things, &distances, 5);
+        let path = temp_path("plot.svg");
+        plot_to_file(&genome, &path).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(text.starts_with("<svg"));
+        assert_eq!(text.matches("<circle").count(), 8);
+    }
+
+    #[test]
+    fn save_and_load_keep_the_best_fitness() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let (things, _) = random_cities(10, 6);
+        let distances = DistanceMatrix::new(&things, Metric::Euclidean);
+        let mut population = Population::new(20, &things, distances, &mut rng);
+        population.data.sort();
+        let path = temp_path("checkpoint.json");
+        population.save(&path).unwrap();
+
+        let mut loaded = Population::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        loaded.data.sort();
+        assert_eq!(loaded.data.len(), 20);
+        assert_eq!(loaded.data[0].data, population.data[0].data);
+        assert_eq!(loaded.data[0].fitness(), population.data[0].fitness());
+    }
+
+    #[test]
+    fn two_opt_never_lengthens_an_asymmetric_tour() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (things, _) = random_cities(8, 7);
+        for _ in 0..200 {
+            let weights: Vec<f64> = (0..64).map(|_| rng.random_range(1.0..100.0)).collect();
+            let distances =
+                Arc::new(DistanceMatrix::from_weights(8, "FULL_MATRIX", &weights).unwrap());
+            let mut tour: Vec<usize> = (0..8).collect();
+            tour.shuffle(&mut rng);
+            for mode in [TwoOptMode::FirstImprovement, TwoOptMode::BestImprovement] {
+                let mut genome = Genome::from_tour(&things, &distances, tour.clone());
+                let before = genome.fitness();
+                genome.two_opt(mode, 1000, &[]);
+                assert!(
+                    genome.fitness() <= before + 1e-9,
+                    "{mode:?} lengthened {tour:?} from {before} to {}",
+                    genome.fitness()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn scramble_keeps_the_cities() {
+        let (things, distances) = random_cities(20, 0);
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..50 {
+            let mut genome = Genome::from_tour(&things, &distances, (0..20).collect());
+            genome.scramble(&mut rng, 3, 1.0);
+            let mut sorted = genome.data.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..20).collect::<Vec<usize>>());
+        }
+    }
+
+    #[test]
+    fn insertion_moves_one_city() {
+        let (things, distances) = random_cities(20, 0);
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..50 {
+            let before: Vec<usize> = (0..20).collect();
+            let mut genome = Genome::from_tour(&things, &distances, before.clone());
+            genome.insert(&mut rng, 1, 1.0);
+            // dropping the moved city leaves both tours in the same order
+            let without = |tour: &[usize], city: usize| -> Vec<usize> {
+                tour.iter().copied().filter(|c| *c != city).collect()
+            };
+            assert!(
+                (0..20).any(|city| without(&before, city) == without(&genome.data, city)),
+                "{:?}",
+                genome.data
+            );
+            assert_eq!(genome.data.len(), 20);
+        }
+    }
+
+    #[test]
+    fn invert_reverses_one_segment() {
+        let (things, distances) = random_cities(20, 0);
+        let before: Vec<usize> = (0..20).collect();
+        for seed in 0..20 {
+            let mut genome = Genome::from_tour(&things, &distances, before.clone());
+            genome.invert(&mut StdRng::seed_from_u64(seed), 1, 1.0);
+
+            // everything outside the first and last changed position is untouched
+            let changed: Vec<usize> = (0..20).filter(|i| genome.data[*i] != before[*i]).collect();
+            let (Some(&start), Some(&end)) = (changed.first(), changed.last()) else {
+                continue;
+            };
+            let mut reversed = before[start..=end].to_vec();
+            reversed.reverse();
+            assert_eq!(genome.data[start..=end], reversed, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn distance_matrix_csv_is_symmetric_with_a_zero_diagonal() {
+        let (things, distances) = random_cities(6, 3);
+        let path = temp_path("matrix.csv");
+        write_distance_matrix(&things, &distances, &path).unwrap();
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let rows: Vec<Vec<f64>> = reader
+            .records()
+            .map(|record| {
+                let record = record.unwrap();
+                record.iter().skip(1).map(|d| d.parse().unwrap()).collect()
+            })
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(rows.len(), 6);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row[i], 0.0);
+            for (j, distance) in row.iter().enumerate() {
+                assert_eq!(*distance, rows[j][i]);
+                assert_eq!(*distance, distances.get(i, j));
+            }
+        }
+    }
+
+    #[test]
+    fn progress_plots_at_most_once_per_interval() {
+        let (things, _) = random_cities(30, 4);
+        let mut rng = StdRng::seed_from_u64(4);
+        let distances = DistanceMatrix::new(&things, Metric::Euclidean);
+        let mut population = Population::new(20, &things, distances, &mut rng);
+        let mut out = Vec::new();
+        let mut progress = TerminalProgress {
+            interval: 10,
+            last: None,
+            out: Some(&mut out),
+            jsonl: None,
+        };
+        let generations = 50;
+        run_evolution(
+            &mut population,
+            &EvolutionConfig::default(),
+            &GenerationLimit(generations),
+            &mut progress,
+            &mut rng,
+        );
+
+        let plots = String::from_utf8(out).unwrap().matches("\x1b[2J").count();
+        assert!(plots >= 1);
+        assert!(plots <= generations.div_ceil(10), "{plots}");
+    }
+
+    #[test]
+    fn stepping_a_runner_matches_solve() {
+        let options = Options {
+            pop_size: 20,
+            generations: 30,
+            ..Default::default()
+        };
+        let mut runner = Runner::new(6, &options);
+        while runner.step() {}
+        assert_eq!(runner.generation(), 31);
+        assert_eq!(runner.best_length(), solve(6, &options));
+    }
+
+    #[test]
+    fn cloned_population_shares_its_context() {
+        let (things, _) = random_cities(10, 1);
+        let distances = DistanceMatrix::new(&things, Metric::Euclidean);
+        let population = Population::new(5, &things, distances, &mut StdRng::seed_from_u64(1));
+        let mut cloned = population.clone();
+        cloned.data[0].data.reverse();
+
+        assert_ne!(cloned.data[0].data, population.data[0].data);
+        assert!(Arc::ptr_eq(&cloned.things, &population.things));
+        assert!(Arc::ptr_eq(&cloned.distances, &population.distances));
+        for (a, b) in cloned.data.iter().zip(&population.data) {
+            assert!(Arc::ptr_eq(&a.things, &population.things));
+            assert!(Arc::ptr_eq(&a.distances, &b.distances));
+        }
+    }
+
+    #[test]
+    fn missing_eof_marker_is_an_error() {
+        let truncated = TSP.strip_suffix("EOF\n").unwrap();
+        let err = parse_tsp(std::io::Cursor::new(truncated)).unwrap_err();
+        assert!(
+            matches!(err, TspParseError::UnexpectedEof("EOF marker")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn missing_section_header_is_an_error() {
+        let input = TSP.replace("NODE_COORD_SECTION\n", "");
+        let err = parse_tsp(std::io::Cursor::new(input)).unwrap_err();
+        assert!(
+            matches!(err, TspParseError::UnexpectedEof("NODE_COORD_SECTION")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn truncated_coordinate_line_names_the_line() {
+        let input = TSP.replace("2 3 4\n", "2 3\n");
+        let err = parse_tsp(std::io::Cursor::new(input)).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                TspParseError::MissingField {
+                    line: 7,
+                    field: "y coordinate"
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn non_numeric_coordinate_names_the_line() {
+        let input = TSP.replace("2 3 4\n", "2 3 four\n");
+        let err = parse_tsp(std::io::Cursor::new(input)).unwrap_err();
+        assert!(
+            matches!(&err, TspParseError::InvalidNumber { line: 7, value } if value == "four"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn dimension_mismatch_is_an_error() {
+        let input = TSP.replace("DIMENSION : 3", "DIMENSION : 4");
+        let err = parse_tsp(std::io::Cursor::new(input)).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                TspParseError::DimensionMismatch {
+                    expected: 4,
+                    found: 3
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn coordinates_may_be_separated_by_several_spaces() {
+        let input = TSP
+            .replace("1 0 0\n", "  1   0    0\n")
+            .replace("2 3 4\n", "2  3\t 4  \n");
+        let (things, distances) = parse_tsp(std::io::Cursor::new(input)).unwrap();
+        let names: Vec<&str> = things.iter().map(|thing| thing.name.as_str()).collect();
+        assert_eq!(names, ["1", "2", "3"]);
+        assert_eq!((things[1].x, things[1].y), (3.0, 4.0));
+        assert_eq!(distances.get(0, 1), 5.0);
+    }
+
+    #[test]
+    fn metrics_on_two_fixed_points() {
+        let a = Thing::new("a".to_string(), 1.0, 2.0);
+        let b = Thing::new("b".to_string(), 4.0, -2.0);
+        assert_eq!(Euclidean.distance(&a, &b), 5.0);
+        assert_eq!(Manhattan.distance(&a, &b), 7.0);
+        assert_eq!(Chebyshev.distance(&a, &b), 4.0);
+    }
+
+    #[test]
+    fn metric_overrides_the_csv_default() {
+        let (_, distances) =
+            parse_instance(std::io::Cursor::new(CSV), Some(Metric::Manhattan)).unwrap();
+        assert_eq!(distances.get(0, 1), 7.0);
+        let metric: Metric = "chebyshev".parse().unwrap();
+        let (_, distances) = parse_instance(std::io::Cursor::new(CSV), Some(metric)).unwrap();
+        assert_eq!(distances.get(0, 1), 4.0);
+    }
+
+    #[test]
+    fn haversine_london_to_edinburgh() {
+        let london = Thing::new("London".to_string(), 51.5074, -0.1278);
+        let edinburgh = Thing::new("Edinburgh".to_string(), 55.9533, -3.1883);
+        let haversine = Haversine {
+            radius: EARTH_RADIUS_KM,
+        };
+        // the great-circle distance is about 534 km
+        let distance = haversine.distance(&london, &edinburgh);
+        assert!((distance - 534.0).abs() < 5.0, "{distance}");
+        assert_eq!(distance, haversine.distance(&edinburgh, &london));
+    }
+
+    #[test]
+    fn sort_puts_the_shortest_tour_first() {
+        let (things, _) = random_cities(10, 2);
+        let distances = DistanceMatrix::new(&things, Metric::Euclidean);
+        let mut population = Population::new(20, &things, distances, &mut StdRng::seed_from_u64(2));
+        population.data.sort();
+        let lengths: Vec<f64> = population.data.iter().map(|g| g.fitness()).collect();
+        assert!(lengths.windows(2).all(|w| w[0] <= w[1]), "{lengths:?}");
+        assert_eq!(genetic::worst(&population.data).fitness(), lengths[19]);
+    }
+
+    #[test]
+    fn recorded_bests_each_beat_the_last() {
+        let (things, _) = random_cities(30, 16);
+        let mut rng = StdRng::seed_from_u64(16);
+        let distances = DistanceMatrix::new(&things, Metric::Euclidean);
+        let mut population = Population::new(20, &things, distances, &mut rng);
+        let config = EvolutionConfig {
+            record_best_genomes: true,
+            ..Default::default()
+        };
+        let result = run_evolution(
+            &mut population,
+            &config,
+            &GenerationLimit(50),
+            &mut |_: usize, _: &Population, _: bool| {},
+            &mut rng,
+        );
+        let lengths: Vec<f64> = result.best_genomes.iter().map(|g| g.fitness()).collect();
+        assert!(lengths.len() >= 2, "{lengths:?}");
+        assert!(
+            lengths
+                .windows(2)
+                .all(|w| Genome::SENSE.is_better(w[1], w[0])),
+            "{lengths:?}"
+        );
+        assert_eq!(*lengths.last().unwrap(), result.fitness);
+    }
+
+    #[test]
+    fn reset_retains_exactly_the_three_best() {
+        let (things, _) = random_cities(12, 5);
+        let mut rng = StdRng::seed_from_u64(5);
+        let distances = DistanceMatrix::new(&things, Metric::Euclidean);
+        let mut population = Population::new(20, &things, distances, &mut rng);
+        population.data.sort();
+        let before: Vec<Vec<usize>> = population.data.iter().map(|g| g.data.clone()).collect();
+        let evaluations = population.state.evaluations;
+
+        population.reset_keep_best(&mut rng, 3);
+
+        let survived = |tour: &Vec<usize>| population.data.iter().any(|g| &g.data == tour);
+        assert!(before[..3].iter().all(survived));
+        assert!(!before[3..].iter().any(survived));
+        assert_eq!(population.data.len(), 20);
+        assert_eq!(population.state.evaluations, evaluations + 17);
+    }
+
+    #[test]
+    fn breeding_pool_has_a_floor() {
+        let (things, _) = random_cities(6, 7);
+        let mut rng = StdRng::seed_from_u64(7);
+        let distances = DistanceMatrix::new(&things, Metric::Euclidean);
+        let mut population = Population::new(3, &things, distances, &mut rng);
+        population.min_breeding_pool = 2;
+        // half of 3 would leave a single parent
+        assert_eq!(population.breeding_pool(), 2);
+
+        let config = EvolutionConfig {
+            elite_count: 1,
+            ..Default::default()
+        };
+        run_evolution(
+            &mut population,
+            &config,
+            &GenerationLimit(5),
+            &mut |_: usize, population: &Population, _: bool| {
+                assert_eq!(population.breeding_pool(), 2);
+                assert_eq!(population.data.len(), 3);
+            },
+            &mut rng,
+        );
+    }
+
+    #[test]
+    fn diverse_restart_keeps_the_least_similar_tour_over_copies_of_the_best() {
+        // cities on a circle, so going round in order is the best tour
+        let things: Arc<[Thing]> = (0..8)
+            .map(|i| {
+                let angle = i as f64 * std::f64::consts::TAU / 8.0;
+                Thing::new(i.to_string(), angle.cos(), angle.sin())
+            })
+            .collect();
+        let distances = Arc::new(DistanceMatrix::new(&things, Metric::Euclidean));
+        let around: Vec<usize> = (0..8).collect();
+        let star = vec![0, 3, 6, 1, 4, 7, 2, 5];
+        let mut data = vec![Genome::from_tour(&things, &distances, around.clone()); 4];
+        data.push(Genome::from_tour(&things, &distances, star.clone()));
+        let mut population = Population::with_data(data, things, distances);
+
+        population.reset_keep_diverse(&mut StdRng::seed_from_u64(1), 1);
+
+        assert_eq!(population.data.len(), 5);
+        assert_eq!(population.data[0].data, around);
+        assert!(population.data.iter().any(|genome| genome.data == star));
+        assert_eq!(population.state.evaluations, 5 + 3);
+    }
+
+    #[test]
+    fn evolved_tours_keep_every_required_edge() {
+        let required = vec![(0, 5), (5, 9), (20, 21), (40, 3)];
+        let options = Options {
+            pop_size: 20,
+            generations: 30,
+            two_opt: 20,
+            required_edges: required.clone(),
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(3);
+        let (mut population, config) = setup(&options, &mut rng);
+        let result = run_evolution(
+            &mut population,
+            &config,
+            &GenerationLimit(options.generations),
+            &mut |_: usize, _: &Population, _: bool| {},
+            &mut rng,
+        );
+
+        let tours = population.data.iter().chain([&result.best]);
+        for tour in tours {
+            for (a, b) in &required {
+                assert!(has_edge(&tour.data, *a, *b), "{a}-{b} in {:?}", tour.data);
+            }
+        }
+    }
+
+    #[test]
+    fn identical_cities_finish_without_evolving() {
+        let things: Arc<[Thing]> = (0..5)
+            .map(|i| Thing::new(i.to_string(), 2.0, 3.0))
+            .collect();
+        let distances = DistanceMatrix::new(&things, Metric::Euclidean);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut population = Population::new(10, &things, distances, &mut rng);
+        let solution = run_evolution(
+            &mut population,
+            &EvolutionConfig::default(),
+            &GenerationLimit(50),
+            &mut |_: usize, _: &Population, _: bool| {},
+            &mut rng,
+        );
+        assert_eq!(solution.fitness, 0.0);
+        assert_eq!(solution.generations, 0);
+
+        let options = Options {
+            input: "same.csv".to_string(),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        report(&solution, &population, &options, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("every tour has length 0"), "{out}");
+        assert!(out.contains("solution: 0 - "), "{out}");
+    }
+
+    #[test]
+    fn diversity_is_the_mean_fraction_of_differing_positions() {
+        let (things, distances) = square();
+        let tour = |data: Vec<usize>| Genome::from_tour(&things, &distances, data);
+        let identical = vec![tour(vec![0, 1, 2, 3]); 3];
+        let population = Population::with_data(identical, things.clone(), distances.clone());
+        assert_eq!(population.diversity(), 0.0);
+
+        // 2, 4 and 4 of 4 positions differ between the pairs
+        let data = vec![
+            tour(vec![0, 1, 2, 3]),
+            tour(vec![1, 0, 2, 3]),
+            tour(vec![3, 2, 1, 0]),
+        ];
+        let population = Population::with_data(data, things.clone(), distances.clone());
+        assert!((population.diversity() - 5.0 / 6.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn random_population_has_more_entropy_than_a_converged_one() {
+        let (things, _) = random_cities(10, 4);
+        let distances = DistanceMatrix::new(&things, Metric::Euclidean);
+        let mut population = Population::new(20, &things, distances, &mut StdRng::seed_from_u64(4));
+        let random = population.entropy();
+        population.data = vec![population.data[0].clone(); 20];
+        // one tour's 10 edges, each equally frequent
+        assert!((population.entropy() - 10f64.log2()).abs() < 1e-12);
+        assert!(random > population.entropy() + 1.0, "{random}");
+    }
+
+    #[test]
+    fn tour_is_most_similar_to_itself_and_least_to_an_edge_disjoint_one() {
+        let (things, distances) = random_cities(5, 1);
+        let tour = |data: Vec<usize>| Genome::from_tour(&things, &distances, data);
+        let around = tour(vec![0, 1, 2, 3, 4]);
+        // edges are undirected, so the reversal is the same tour
+        let reversed = tour(vec![4, 3, 2, 1, 0]);
+        let star = tour(vec![0, 2, 4, 1, 3]);
+        assert_eq!(around.similarity(&around), 1.0);
+        assert_eq!(around.similarity(&reversed), 1.0);
+        assert_eq!(around.similarity(&star), 0.0);
+    }
+
+    #[test]
+    fn adaptive_order_crossover_keeps_tours_valid_on_large_and_tiny_instances() {
+        for n in [131, 4] {
+            for seed in 0..20 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut a: Vec<usize> = (0..n).collect();
+                let mut b = a.clone();
+                a.shuffle(&mut rng);
+                b.shuffle(&mut rng);
+                let (a, b) = cross(&a, &b, CrossoverKind::AdaptiveOrder, seed);
+                for child in [a, b] {
+                    let mut sorted = child.clone();
+                    sorted.sort_unstable();
+                    assert_eq!(sorted, (0..n).collect::<Vec<_>>(), "{child:?}");
+                }
+            }
+        }
+    }
 }
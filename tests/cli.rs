@@ -0,0 +1,72 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn gen_rs(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_gen-rs"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "gen-rs {args:?} failed");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn jsonl_on_stdout_keeps_every_line_parseable() {
+    let stdout = gen_rs(&[
+        "--seed",
+        "1",
+        "tsp",
+        "--generations",
+        "3",
+        "--pop-size",
+        "10",
+        "--jsonl",
+        "-",
+    ]);
+    assert!(!stdout.is_empty());
+    for line in stdout.lines() {
+        let progress: serde_json::Value =
+            serde_json::from_str(line).unwrap_or_else(|err| panic!("{line:?} is not json: {err}"));
+        assert!(progress["fitness"].is_f64());
+    }
+}
+
+#[test]
+fn knapsack_reads_items_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gen-rs"))
+        .args(["--seed", "1", "knapsack", "-", "--generations", "20"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"name,value,weight,volume\nLantern,40,100,10\nAnvil,1,9000,10\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Lantern"), "{stdout}");
+    assert!(!stdout.contains("Laptop"), "{stdout}");
+}
+
+#[test]
+fn logged_seed_reproduces_the_run() {
+    let args = ["tsp", "--generations", "5", "--pop-size", "10"];
+    let first = gen_rs(&args);
+    let seed = first
+        .lines()
+        .find_map(|line| line.strip_prefix("tsp seed: "))
+        .unwrap();
+    let again = gen_rs(&[&["--seed", seed][..], &args[..]].concat());
+    let solution = |stdout: &str| {
+        stdout
+            .lines()
+            .find(|line| line.starts_with("solution:"))
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(solution(&first), solution(&again));
+}
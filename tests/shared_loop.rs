@@ -0,0 +1,32 @@
+use gen_rs::{knapsack, tsp};
+
+// best packing of the demo items within both limits
+const KNAPSACK_OPTIMUM: f64 = 1310.0;
+
+// best known tour length for data/xqf131.tsp
+const XQF131_OPTIMUM: f64 = 564.0;
+
+// both problems run through genetic::run_evolution
+#[test]
+fn knapsack_and_tsp_run_through_the_shared_loop() {
+    let options = knapsack::Options {
+        generations: 300,
+        ..Default::default()
+    };
+    let value = knapsack::solve(1, &options);
+    assert!(
+        value >= 0.9 * KNAPSACK_OPTIMUM,
+        "packed value {value} is more than 10% below {KNAPSACK_OPTIMUM}"
+    );
+
+    let options = tsp::Options {
+        pop_size: 100,
+        generations: 100,
+        ..Default::default()
+    };
+    let length = tsp::solve(1, &options);
+    assert!(
+        length <= 1.5 * XQF131_OPTIMUM,
+        "tour length {length} is more than 50% above {XQF131_OPTIMUM}"
+    );
+}
@@ -0,0 +1,65 @@
+use gen_rs::tsp;
+
+// best known tour length for data/xqf131.tsp
+const XQF131_OPTIMUM: f64 = 564.0;
+
+// median best tour length over seeds 1..=runs
+fn median_length(options: &tsp::Options, runs: u64) -> f64 {
+    let mut lengths: Vec<f64> = (1..=runs).map(|seed| tsp::solve(seed, options)).collect();
+    lengths.sort_by(f64::total_cmp);
+    lengths[lengths.len() / 2]
+}
+
+// the median over several runs, so one unlucky seed doesn't fail the test
+fn assert_improves_over_baseline(options: &tsp::Options, baseline: f64, runs: u64) {
+    let median = median_length(options, runs);
+    assert!(
+        median < baseline,
+        "median tour length {median} does not beat the baseline {baseline}"
+    );
+}
+
+#[test]
+fn xqf131_within_20_percent_of_optimum() {
+    let options = tsp::Options {
+        pop_size: 200,
+        generations: 300,
+        ..Default::default()
+    };
+    let length = tsp::solve(1, &options);
+    assert!(
+        length <= 1.2 * XQF131_OPTIMUM,
+        "tour length {length} is more than 20% above {XQF131_OPTIMUM}"
+    );
+}
+
+#[test]
+fn two_opt_beats_plain_runs() {
+    let plain = tsp::Options {
+        pop_size: 100,
+        generations: 100,
+        two_opt: 0,
+        ..Default::default()
+    };
+    let baseline = median_length(&plain, 3);
+    let two_opt = tsp::Options {
+        two_opt: 100,
+        ..plain
+    };
+    assert_improves_over_baseline(&two_opt, baseline, 3);
+}
+
+#[test]
+fn uk_cities_tour_is_measured_in_kilometres() {
+    let options = tsp::Options {
+        input: "data/uk-cities.csv".to_string(),
+        pop_size: 100,
+        generations: 200,
+        ..Default::default()
+    };
+    // lat,lon columns are measured with haversine, and a round trip of 49
+    // cities from Plymouth to Aberdeen is a few thousand km. read as plain
+    // degrees it would come to well under 100
+    let length = tsp::solve(1, &options);
+    assert!((2000.0..6000.0).contains(&length), "{length}");
+}